@@ -0,0 +1,76 @@
+use crate::executor::{reactor, TaskId};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Reactor(reactor::Error),
+    /// The executor has been dropped.
+    Shutdown,
+    /// The task was dropped before completion.
+    Cancelled,
+    /// The task panicked, with the panic message.
+    Panicked(String),
+    /// The executor was shut down by the `FailurePolicy`, after the task failed.
+    TaskFailed(TaskId),
+    Timer(reactor::Error),
+}
+
+impl Error {
+    /// Turns the payload of a panic into `Panicked`.
+    pub(crate) fn panicked(payload: &(dyn std::any::Any + Send)) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            String::from("unknown panic")
+        };
+        Error::Panicked(message)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Reactor(e) => write!(f, "reactor error: {}", e),
+            Error::Shutdown => write!(f, "executor shut down"),
+            Error::Cancelled => write!(f, "task cancelled"),
+            Error::Panicked(message) => write!(f, "task panicked: {}", message),
+            Error::TaskFailed(id) => write!(f, "task {:?} failed", id),
+            Error::Timer(e) => write!(f, "timer error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Reactor(e) | Error::Timer(e) => Some(e),
+            Error::Shutdown | Error::Cancelled | Error::Panicked(_) | Error::TaskFailed(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<reactor::Error> for Error {
+    fn from(e: reactor::Error) -> Self {
+        Error::Reactor(e)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Reactor(e) | Error::Timer(e) => e.into(),
+            e => std::io::Error::other(e),
+        }
+    }
+}
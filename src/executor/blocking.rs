@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// How long an idle thread waits for a job before it exits.
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+pub(super) type Job = Box<dyn FnOnce() + Send>;
+pub(super) type Callback = Arc<dyn Fn() + Send + Sync>;
+
+/// Runs blocking jobs on up to `max_threads` threads, started on demand.
+pub(super) struct Pool {
+    shared: Arc<Shared>,
+    max_threads: usize,
+    name: String,
+    on_start: Option<Callback>,
+    on_stop: Option<Callback>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+    // Idle threads which have been notified, but are yet to wake up.
+    notified: usize,
+    shutdown: bool,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn work(&self) {
+        let mut state = self.state();
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                job();
+                state = self.state();
+                continue;
+            }
+            if state.shutdown {
+                break;
+            }
+            state.idle += 1;
+            let mut timed_out = false;
+            while state.notified == 0 && !state.shutdown && !timed_out {
+                let (guard, res) = self
+                    .condvar
+                    .wait_timeout(state, KEEP_ALIVE)
+                    .unwrap_or_else(PoisonError::into_inner);
+                state = guard;
+                timed_out = res.timed_out();
+            }
+            state.idle -= 1;
+            if state.notified > 0 {
+                state.notified -= 1;
+            } else if timed_out && state.queue.is_empty() {
+                break;
+            }
+        }
+        state.threads -= 1;
+    }
+}
+
+impl Pool {
+    /// `on_start` and `on_stop` are called on every thread of the pool, when
+    /// it starts and before it exits.
+    pub fn new(
+        max_threads: usize,
+        name: String,
+        on_start: Option<Callback>,
+        on_stop: Option<Callback>,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Default::default(),
+                condvar: Condvar::new(),
+            }),
+            max_threads: std::cmp::max(max_threads, 1),
+            name,
+            on_start,
+            on_stop,
+        }
+    }
+
+    /// Queues `job`, waking an idle thread or starting a new one if the pool
+    /// is not full. Otherwise, the job waits for a thread to be done.
+    pub fn execute(&self, job: Job) {
+        let mut state = self.shared.state();
+        state.queue.push_back(job);
+        if state.idle > state.notified {
+            state.notified += 1;
+            self.shared.condvar.notify_one();
+            return;
+        }
+        if state.threads == self.max_threads {
+            return;
+        }
+        let shared = self.shared.clone();
+        let (on_start, on_stop) = (self.on_start.clone(), self.on_stop.clone());
+        let res = std::thread::Builder::new()
+            .name(self.name.clone())
+            .spawn(move || {
+                if let Some(on_start) = on_start {
+                    on_start();
+                }
+                shared.work();
+                if let Some(on_stop) = on_stop {
+                    on_stop();
+                }
+            });
+        match res {
+            Ok(_) => state.threads += 1,
+            Err(e) => {
+                log::error!("blocking pool: {}", e);
+                if state.threads == 0 {
+                    // Dropping the job cancels it.
+                    let job = state.queue.pop_back();
+                    drop(state);
+                    drop(job);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // Cancel the jobs which have not started. The running ones are left to
+        // complete on their own.
+        let queued = {
+            let mut state = self.shared.state();
+            state.shutdown = true;
+            std::mem::take(&mut state.queue)
+        };
+        self.shared.condvar.notify_all();
+        drop(queued);
+    }
+}
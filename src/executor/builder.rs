@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use crate::executor::blocking::{Callback, Pool};
+use crate::executor::{reactor, Executor, FailurePolicy, BLOCKING_THREADS, BUDGET};
+
+/// Configures an `Executor`.
+pub struct Builder {
+    reactor: reactor::Builder,
+    budget: usize,
+    blocking_threads: usize,
+    thread_name: String,
+    on_thread_start: Option<Callback>,
+    on_thread_stop: Option<Callback>,
+    failure_policy: FailurePolicy,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            reactor: reactor::Builder::new(),
+            budget: BUDGET,
+            blocking_threads: BLOCKING_THREADS,
+            thread_name: String::from("dope-blocking"),
+            on_thread_start: None,
+            on_thread_stop: None,
+            failure_policy: FailurePolicy::new(),
+        }
+    }
+
+    /// Uses `backend` instead of the default one of the platform.
+    pub fn backend<B: reactor::Backend + 'static>(mut self, backend: B) -> Self {
+        self.reactor = self.reactor.backend(backend);
+        self
+    }
+
+    /// The number of events fetched per poll at first. See
+    /// `reactor::Builder::event_capacity`.
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.reactor = self.reactor.event_capacity(capacity);
+        self
+    }
+
+    pub fn max_event_capacity(mut self, capacity: usize) -> Self {
+        self.reactor = self.reactor.max_event_capacity(capacity);
+        self
+    }
+
+    /// The number of tasks polled before the reactor is turned, while tasks
+    /// keep being woken.
+    pub fn budget(mut self, budget: usize) -> Self {
+        self.budget = std::cmp::max(budget, 1);
+        self
+    }
+
+    /// The number of threads running `spawn_blocking` jobs at most.
+    pub fn blocking_threads(mut self, threads: usize) -> Self {
+        self.blocking_threads = std::cmp::max(threads, 1);
+        self
+    }
+
+    /// The name of the threads started by the executor.
+    pub fn thread_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.thread_name = name.into();
+        self
+    }
+
+    /// Called on every thread started by the executor, before it runs
+    /// anything.
+    pub fn on_thread_start<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_thread_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Called on every thread started by the executor, before it exits.
+    pub fn on_thread_stop<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_thread_stop = Some(Arc::new(f));
+        self
+    }
+
+    pub fn failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.failure_policy = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<Executor, crate::Error> {
+        let blocking = Pool::new(
+            self.blocking_threads,
+            self.thread_name,
+            self.on_thread_start,
+            self.on_thread_stop,
+        );
+        let executor = Executor::build(self.reactor.build()?, self.budget, blocking);
+        executor.set_failure_policy(self.failure_policy);
+        Ok(executor)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,262 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use futures::FutureExt;
+
+use crate::executor::blocking::Job;
+use crate::executor::policy::Supervisor;
+
+/// Identifies a spawned task, in failure reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TaskId(pub(super) u64);
+
+struct Shared<T> {
+    output: Option<Result<T, crate::Error>>,
+    waker: Option<Waker>,
+    // The waker of the task itself, to drop it promptly once aborted.
+    task: Option<Waker>,
+    aborted: bool,
+}
+
+impl<T> Shared<T> {
+    fn new() -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            output: None,
+            waker: None,
+            task: None,
+            aborted: false,
+        }))
+    }
+}
+
+/// Resolves to the output of a spawned task.
+///
+/// Dropping the handle detaches the task, which keeps running.
+pub struct JoinHandle<T> {
+    id: TaskId,
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+/// Aborts a task without holding on to its output.
+#[derive(Clone)]
+pub struct AbortHandle {
+    shared: Arc<dyn Abort + Send + Sync>,
+}
+
+trait Abort {
+    fn abort(&self);
+}
+
+fn lock<T>(shared: &Mutex<Shared<T>>) -> MutexGuard<'_, Shared<T>> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<T> Abort for Mutex<Shared<T>> {
+    fn abort(&self) {
+        let mut shared = lock(self);
+        if shared.output.is_some() || shared.aborted {
+            return;
+        }
+        shared.aborted = true;
+        let waker = shared.waker.take();
+        let task = shared.task.take();
+        drop(shared);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        if let Some(task) = task {
+            task.wake();
+        }
+    }
+}
+
+impl AbortHandle {
+    /// Drops the task at its next scheduling point, and makes its
+    /// `JoinHandle` resolve to `Error::Cancelled`. Does nothing if the task
+    /// has already completed.
+    pub fn abort(&self) {
+        self.shared.abort();
+    }
+}
+
+/// Hands the result over to the `JoinHandle`, or `Cancelled` if the task is
+/// dropped before completion.
+struct Completer<T> {
+    shared: Option<Arc<Mutex<Shared<T>>>>,
+}
+
+impl<T> Completer<T> {
+    /// Returns whether the task has been aborted, or keeps its waker to be
+    /// woken when it is.
+    fn poll_aborted(&self, cx: &Context<'_>) -> bool {
+        let mut shared = match &self.shared {
+            Some(shared) => lock(shared),
+            None => return false,
+        };
+        if shared.aborted {
+            return true;
+        }
+        match &shared.task {
+            Some(task) if task.will_wake(cx.waker()) => {}
+            _ => shared.task = Some(cx.waker().clone()),
+        }
+        false
+    }
+
+    fn aborted(&self) -> bool {
+        self.shared
+            .as_ref()
+            .is_some_and(|shared| lock(shared).aborted)
+    }
+
+    fn complete(&mut self, output: Result<T, crate::Error>) {
+        if let Some(shared) = self.shared.take() {
+            let mut shared = lock(&shared);
+            shared.output = Some(output);
+            let waker = shared.waker.take();
+            drop(shared);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        self.complete(Err(crate::Error::Cancelled));
+    }
+}
+
+impl<T: 'static> JoinHandle<T> {
+    /// Wraps `future` into a task, which reports its output to the returned
+    /// handle, and failures to `supervisor`. `check` finds the error in an
+    /// output which is a failure. The task is `Send` if `future` and `T` are.
+    pub(super) fn new<F>(
+        id: TaskId,
+        future: F,
+        supervisor: Arc<Supervisor>,
+        check: fn(&T) -> Option<&crate::Error>,
+    ) -> (impl Future<Output = ()>, Self)
+    where
+        F: Future<Output = T> + 'static,
+    {
+        let shared = Shared::new();
+        let mut completer = Completer {
+            shared: Some(shared.clone()),
+        };
+        let mut future = Box::pin(AssertUnwindSafe(future).catch_unwind());
+        let task = async move {
+            let output = futures::future::poll_fn(|cx| {
+                if completer.poll_aborted(cx) {
+                    return Poll::Ready(None);
+                }
+                future.as_mut().poll(cx).map(Some)
+            })
+            .await;
+            let output = match output {
+                Some(Ok(output)) => {
+                    if let Some(e) = check(&output) {
+                        supervisor.report(id, e);
+                    }
+                    Ok(output)
+                }
+                Some(Err(payload)) => {
+                    if !supervisor.catch_panics() {
+                        std::panic::resume_unwind(payload);
+                    }
+                    let e = crate::Error::panicked(&*payload);
+                    supervisor.report(id, &e);
+                    Err(e)
+                }
+                None => return,
+            };
+            completer.complete(output);
+        };
+        (task, Self { id, shared })
+    }
+}
+
+impl<T: Send + 'static> JoinHandle<T> {
+    /// Wraps `f` into a job for the blocking pool, which reports its output to
+    /// the returned handle, and panics to `supervisor`. The job does nothing
+    /// if it is aborted before it starts.
+    pub(super) fn blocking<F>(id: TaskId, f: F, supervisor: Arc<Supervisor>) -> (Job, Self)
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let shared = Shared::new();
+        let mut completer = Completer {
+            shared: Some(shared.clone()),
+        };
+        let job = Box::new(move || {
+            if completer.aborted() {
+                return;
+            }
+            // There is nothing to unwind through, so panics are always caught.
+            let output = match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+                Ok(output) => Ok(output),
+                Err(payload) => {
+                    let e = crate::Error::panicked(&*payload);
+                    supervisor.report(id, &e);
+                    Err(e)
+                }
+            };
+            completer.complete(output);
+        });
+        (job, Self { id, shared })
+    }
+}
+
+impl<T: 'static> JoinHandle<T> {
+    /// Drops the task at its next scheduling point. The handle resolves to
+    /// `Error::Cancelled`, unless the task has already completed.
+    pub fn abort(&self) {
+        self.shared.abort();
+    }
+
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn abort_handle(&self) -> AbortHandle
+    where
+        T: Send,
+    {
+        AbortHandle {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> JoinHandle<T> {
+    /// A handle for a task which an executor has refused, as it has been
+    /// dropped or shut down. It resolves to `Error::Shutdown`.
+    pub(super) fn shut_down() -> Self {
+        let shared = Shared::new();
+        lock(&shared).output = Some(Err(crate::Error::Shutdown));
+        Self {
+            id: TaskId(0),
+            shared,
+        }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, crate::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = lock(&self.shared);
+        match shared.output.take() {
+            Some(output) => Poll::Ready(output),
+            None if shared.aborted => Poll::Ready(Err(crate::Error::Cancelled)),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -1,16 +1,35 @@
+mod blocking;
+mod builder;
+mod join_handle;
+pub mod multi_thread;
+mod policy;
 pub mod reactor;
 pub mod scheduler;
 
+use blocking::Pool;
+pub use builder::Builder;
+pub use join_handle::{AbortHandle, JoinHandle, TaskId};
+pub use multi_thread::MultiThread;
+pub use policy::FailurePolicy;
+use policy::Supervisor;
 use reactor::Reactor;
 use scheduler::Scheduler;
 
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     rc::{Rc, Weak},
 };
 
+/// The number of tasks polled before the reactor is turned, by default.
+const BUDGET: usize = 128;
+/// The number of threads running `spawn_blocking` jobs at most, by default.
+const BLOCKING_THREADS: usize = 16;
+
 pub struct Executor {
     inner: Rc<RefCell<Inner>>,
 }
@@ -20,45 +39,136 @@ pub struct Handle {
     inner: Weak<RefCell<Inner>>,
 }
 
-#[derive(Debug, Fail)]
-enum Error {
-    #[fail(display = "dropped pointer")]
-    DroppedPtr,
-}
-
 impl Handle {
-    pub fn reactor(&self) -> Result<reactor::Handle, failure::Error> {
+    pub fn reactor(&self) -> Result<reactor::Handle, crate::Error> {
         Ok(self
             .inner
             .upgrade()
-            .ok_or(Error::DroppedPtr)?
+            .ok_or(crate::Error::Shutdown)?
             .borrow()
             .reactor
             .handle())
     }
 
-    pub fn spawn<F>(&self, future: F) -> Result<(), failure::Error>
+    /// Runs `future` as a separate task. If the executor has been dropped or
+    /// shut down, the returned handle resolves to `Error::Shutdown`.
+    ///
+    /// The future has to be `Send`, as it would on a `MultiThread` runtime.
+    /// Use `spawn_local` otherwise.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
     where
-        F: Future<Output = Result<(), failure::Error>> + 'static,
+        F: Future + Send + 'static,
+        F::Output: Send,
     {
-        self.inner
-            .upgrade()
-            .ok_or(Error::DroppedPtr)?
-            .borrow_mut()
-            .scheduler
-            .schedule(Box::pin(future));
-        Ok(())
+        self.spawn_checked(future, |_| None)
+    }
+
+    /// Like `spawn`, for futures which are not `Send`.
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        self.spawn_checked(future, |_| None)
+    }
+
+    /// Like `spawn`, but an error returned by the task is reported to the
+    /// `FailurePolicy`, as a panic would be.
+    pub fn spawn_fallible<F, T>(&self, future: F) -> JoinHandle<Result<T, crate::Error>>
+    where
+        F: Future<Output = Result<T, crate::Error>> + Send + 'static,
+        T: Send,
+    {
+        self.spawn_checked(future, |output| output.as_ref().err())
+    }
+
+    /// Runs `f` on a bounded pool of threads, so that blocking calls do not
+    /// stall the event loop. The task awaiting the handle is woken through the
+    /// notifier of the reactor. Aborting the handle only prevents `f` from
+    /// starting.
+    pub fn spawn_blocking<F, T>(&self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = match self.inner.upgrade() {
+            Some(inner) if inner.borrow().accepts() => inner,
+            _ => return JoinHandle::shut_down(),
+        };
+        let borrowed = inner.borrow();
+        let id = borrowed.next_id();
+        let (job, handle) = JoinHandle::blocking(id, f, borrowed.supervisor.clone());
+        borrowed.blocking.execute(job);
+        handle
+    }
+
+    /// Stops accepting new tasks, which resolve to `Error::Shutdown`, and
+    /// gives the others up to `grace` to complete. Past that, they are
+    /// dropped. Either way, `block_on` and `run` return once no task is
+    /// left, with `Error::Shutdown` unless their future has completed.
+    pub fn shutdown(&self, grace: chrono::Duration) {
+        if let Some(inner) = self.inner.upgrade() {
+            let borrowed = inner.borrow();
+            if borrowed.deadline.get().is_none() {
+                let grace = grace.to_std().unwrap_or_default();
+                borrowed.deadline.set(Some(Instant::now() + grace));
+            }
+        }
+    }
+
+    fn spawn_checked<F>(
+        &self,
+        future: F,
+        check: fn(&F::Output) -> Option<&crate::Error>,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        let inner = match self.inner.upgrade() {
+            Some(inner) if inner.borrow().accepts() => inner,
+            _ => return JoinHandle::shut_down(),
+        };
+        // Tasks may spawn others while the scheduler is running them.
+        let borrowed = inner.borrow();
+        let id = borrowed.next_id();
+        let (task, handle) = JoinHandle::new(id, future, borrowed.supervisor.clone(), check);
+        borrowed.scheduler.schedule(Box::pin(task));
+        handle
     }
 }
 
 impl Executor {
-    pub fn new() -> Result<Self, failure::Error> {
-        Ok(Self {
+    pub fn new() -> Result<Self, crate::Error> {
+        Builder::new().build()
+    }
+
+    pub fn with_backend<B: reactor::Backend + 'static>(backend: B) -> Result<Self, crate::Error> {
+        Builder::new().backend(backend).build()
+    }
+
+    /// Runs on a `Reactor` configured by a `reactor::Builder`, with the
+    /// defaults of `Builder` otherwise.
+    pub fn from_reactor(reactor: Reactor) -> Self {
+        let blocking = Pool::new(BLOCKING_THREADS, String::from("dope-blocking"), None, None);
+        Self::build(reactor, BUDGET, blocking)
+    }
+
+    fn build(reactor: Reactor, budget: usize, blocking: Pool) -> Self {
+        let scheduler = Scheduler::new(reactor.notifier());
+        Self {
             inner: Rc::new(RefCell::new(Inner {
-                reactor: Reactor::new()?,
-                scheduler: Scheduler::default(),
+                reactor,
+                scheduler,
+                supervisor: Default::default(),
+                blocking,
+                budget,
+                next_id: Cell::new(0),
+                deadline: Cell::new(None),
             })),
-        })
+        }
+    }
+
+    pub fn set_failure_policy(&self, policy: FailurePolicy) {
+        self.inner.borrow().supervisor.set_policy(policy);
     }
 
     pub fn handle(&self) -> Handle {
@@ -67,24 +177,75 @@ impl Executor {
         }
     }
 
-    pub fn block_on<F>(&self, mut future: F) -> Result<F::Output, failure::Error>
+    pub fn block_on<F>(&self, mut future: F) -> Result<F::Output, crate::Error>
     where
         F: Future,
     {
-        use std::task::{Context, Poll};
-
         let mut future = unsafe { Pin::new_unchecked(&mut future) };
+        self.drive(|cx| future.as_mut().poll(cx))
+    }
+
+    /// Runs the spawned tasks until none is left.
+    pub fn run(&self) -> Result<(), crate::Error> {
+        self.drive(|_| match self.inner.borrow().scheduler.task_count() {
+            0 => Poll::Ready(()),
+            _ => Poll::Pending,
+        })
+    }
+
+    /// Polls `root` along with the spawned tasks, until it is ready.
+    fn drive<T, F>(&self, mut root: F) -> Result<T, crate::Error>
+    where
+        F: FnMut(&mut Context<'_>) -> Poll<T>,
+    {
         let waker = self.inner.borrow().scheduler.waker();
         let mut cx = Context::from_waker(&waker);
 
         loop {
-            match future.as_mut().poll(&mut cx) {
-                Poll::Ready(e) => return Ok(e),
-                Poll::Pending => {}
+            if let Poll::Ready(output) = root(&mut cx) {
+                return Ok(output);
             }
 
-            self.inner.borrow().scheduler.tick();
-            self.inner.borrow_mut().reactor.poll()?;
+            let budget = self.inner.borrow().budget;
+            self.inner.borrow().scheduler.run(budget);
+            let failed = self.inner.borrow().supervisor.failed();
+            if let Some(id) = failed {
+                self.inner.borrow().scheduler.clear();
+                return Err(crate::Error::TaskFailed(id));
+            }
+            let deadline = self.inner.borrow().deadline.get();
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    let done = self.inner.borrow().scheduler.task_count() == 0;
+                    if done || remaining.is_zero() {
+                        self.inner.borrow().scheduler.cancel();
+                        // The future may have been waiting for the last tasks.
+                        return match root(&mut cx) {
+                            Poll::Ready(output) => Ok(output),
+                            Poll::Pending => Err(crate::Error::Shutdown),
+                        };
+                    }
+                    // A deadline too far off to be represented is no limit.
+                    chrono::Duration::from_std(remaining).ok()
+                }
+                None => None,
+            };
+            // Only block when no spawned task is waiting to run. Otherwise, the
+            // budget ran out, and pending events are handled before going on.
+            let park = self.inner.borrow().scheduler.park();
+            let res = if park {
+                let res = self.inner.borrow_mut().reactor.poll(timeout);
+                self.inner.borrow().scheduler.unpark();
+                res
+            } else {
+                self.inner.borrow_mut().reactor.turn()
+            };
+            match res {
+                Ok(()) | Err(reactor::Error::Interrupted) => {}
+                Err(reactor::Error::StaleKey(key)) => log::warn!("stale key: {:?}", key),
+                Err(e) => return Err(e.into()),
+            }
         }
     }
 }
@@ -92,4 +253,29 @@ impl Executor {
 struct Inner {
     reactor: Reactor,
     scheduler: Scheduler,
+    supervisor: Arc<Supervisor>,
+    blocking: Pool,
+    budget: usize,
+    next_id: Cell<u64>,
+    // Set once shut down, until when the remaining tasks may run.
+    deadline: Cell<Option<Instant>>,
+}
+
+impl Inner {
+    fn accepts(&self) -> bool {
+        self.supervisor.failed().is_none() && self.deadline.get().is_none()
+    }
+
+    fn next_id(&self) -> TaskId {
+        let id = TaskId(self.next_id.get() + 1);
+        self.next_id.set(id.0);
+        id
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Tasks release their registrations while the reactor is still there.
+        self.scheduler.cancel();
+    }
 }
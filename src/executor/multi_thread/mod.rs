@@ -0,0 +1,428 @@
+mod task;
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, TryLockError, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+
+use crate::executor::policy::Supervisor;
+use crate::executor::reactor::{self, Notifier, Reactor};
+use crate::executor::{FailurePolicy, JoinHandle, TaskId, BUDGET};
+use slab::Slab;
+use task::Task;
+
+type Queue = Mutex<VecDeque<Arc<Task>>>;
+
+thread_local! {
+    // The runtime and the index of the worker running on this thread.
+    static WORKER: Cell<Option<(*const Shared, usize)>> = const { Cell::new(None) };
+}
+
+/// Runs `Send` tasks on a pool of worker threads.
+///
+/// Every worker has a run queue of its own, and steals half of the tasks of
+/// another one once it runs out of them. Idle workers take turns at driving
+/// the reactor, which is shared by all of them.
+pub struct MultiThread {
+    shared: Arc<Shared>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+#[derive(Clone)]
+pub struct Handle {
+    shared: Weak<Shared>,
+}
+
+struct Shared {
+    // Tasks spawned or woken outside of the workers.
+    injector: Queue,
+    queues: Vec<Queue>,
+    idle: Mutex<Idle>,
+    condvar: Condvar,
+    driver: Mutex<Reactor>,
+    // Whether a worker is blocked on the reactor.
+    driving: AtomicBool,
+    reactor: reactor::Handle,
+    notifier: Notifier,
+    shutdown: AtomicBool,
+    fault: Mutex<Option<Fault>>,
+    // Threads in `block_on`, to be woken on shutdown.
+    blocked: Mutex<Vec<Thread>>,
+    supervisor: Arc<Supervisor>,
+    next_id: AtomicU64,
+    // Every task yet to be dropped, including those parked on the reactor.
+    tasks: Mutex<Slab<Weak<Task>>>,
+}
+
+/// The workers waiting for `condvar`.
+#[derive(Default)]
+struct Idle {
+    sleeping: usize,
+    // Sleeping workers which have been notified, but are yet to wake up.
+    notified: usize,
+}
+
+/// Stops the workers.
+enum Fault {
+    Panic(Box<dyn Any + Send>),
+    Reactor(reactor::Error),
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Shared {
+    /// The index of the worker of this runtime on the current thread.
+    fn worker(&self) -> Option<usize> {
+        WORKER.with(|worker| match worker.get() {
+            Some((shared, index)) if std::ptr::eq(shared, self) => Some(index),
+            _ => None,
+        })
+    }
+
+    fn push(&self, task: Arc<Task>) {
+        match self.worker() {
+            Some(index) => lock(&self.queues[index]).push_back(task),
+            None => lock(&self.injector).push_back(task),
+        }
+        if !self.wake_sleeper() && self.driving.load(Ordering::SeqCst) {
+            if let Err(e) = self.notifier.notify() {
+                log::error!("notify: {}", e);
+            }
+        }
+    }
+
+    /// Wakes a worker waiting for tasks. Returns whether there was one which
+    /// had not been notified yet.
+    fn wake_sleeper(&self) -> bool {
+        let mut idle = lock(&self.idle);
+        if idle.sleeping > idle.notified {
+            idle.notified += 1;
+            self.condvar.notify_one();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn has_tasks(&self) -> bool {
+        !lock(&self.injector).is_empty() || self.queues.iter().any(|queue| !lock(queue).is_empty())
+    }
+
+    fn next_task(&self, index: usize) -> Option<Arc<Task>> {
+        if let Some(task) = lock(&self.queues[index]).pop_front() {
+            return Some(task);
+        }
+        if let Some(task) = lock(&self.injector).pop_front() {
+            return Some(task);
+        }
+        self.steal(index)
+    }
+
+    /// Takes the back half of the queue of another worker.
+    fn steal(&self, index: usize) -> Option<Arc<Task>> {
+        let count = self.queues.len();
+        for offset in 1..count {
+            let mut stolen = {
+                let mut victim = lock(&self.queues[(index + offset) % count]);
+                let len = victim.len();
+                victim.split_off(len / 2)
+            };
+            if let Some(task) = stolen.pop_front() {
+                lock(&self.queues[index]).extend(stolen);
+                return Some(task);
+            }
+        }
+        None
+    }
+
+    fn try_driver(&self) -> Option<MutexGuard<'_, Reactor>> {
+        match self.driver.try_lock() {
+            Ok(reactor) => Some(reactor),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    fn work(&self, index: usize) {
+        let mut polled = 0;
+        while !self.shutdown.load(Ordering::SeqCst) {
+            if let Some(task) = self.next_task(index) {
+                task.run(self);
+                polled += 1;
+                if polled % BUDGET == 0 {
+                    self.check_failed();
+                    // Keep handling events while every worker is busy.
+                    if let Some(mut reactor) = self.try_driver() {
+                        let res = reactor.turn();
+                        drop(reactor);
+                        self.check(res);
+                        self.wake_sleeper();
+                    }
+                }
+                continue;
+            }
+            self.check_failed();
+            match self.try_driver() {
+                Some(reactor) => self.drive(reactor),
+                None => self.sleep(),
+            }
+        }
+    }
+
+    /// Blocks on the reactor, unless a task has been queued meanwhile.
+    fn drive(&self, mut reactor: MutexGuard<'_, Reactor>) {
+        self.driving.store(true, Ordering::SeqCst);
+        // Tasks queued before `driving` was set did not notify the reactor.
+        let res = if self.has_tasks() || self.shutdown.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            reactor.poll(None)
+        };
+        self.driving.store(false, Ordering::SeqCst);
+        drop(reactor);
+        self.check(res);
+        // Another worker takes over the reactor while this one runs the tasks.
+        self.wake_sleeper();
+    }
+
+    fn sleep(&self) {
+        let mut idle = lock(&self.idle);
+        // Tasks queued before the worker counts as sleeping did not wake it.
+        if self.has_tasks() {
+            return;
+        }
+        idle.sleeping += 1;
+        while idle.notified == 0 && !self.shutdown.load(Ordering::SeqCst) {
+            idle = self
+                .condvar
+                .wait(idle)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        idle.notified = idle.notified.saturating_sub(1);
+        idle.sleeping -= 1;
+    }
+
+    fn check(&self, res: Result<(), reactor::Error>) {
+        match res {
+            Ok(()) | Err(reactor::Error::Interrupted) => {}
+            Err(reactor::Error::StaleKey(key)) => log::warn!("stale key: {:?}", key),
+            Err(e) => self.fail(Fault::Reactor(e)),
+        }
+    }
+
+    fn check_failed(&self) {
+        if self.supervisor.failed().is_some() {
+            self.stop();
+        }
+    }
+
+    fn fail(&self, fault: Fault) {
+        lock(&self.fault).get_or_insert(fault);
+        self.stop();
+    }
+
+    fn stop(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // A worker about to sleep either sees `shutdown`, or is waiting.
+        drop(lock(&self.idle));
+        self.condvar.notify_all();
+        if let Err(e) = self.notifier.notify() {
+            log::error!("notify: {}", e);
+        }
+        for thread in lock(&self.blocked).iter() {
+            thread.unpark();
+        }
+    }
+
+    /// The reason why the workers have stopped, if they have.
+    fn stopped(&self) -> Option<crate::Error> {
+        let fault = lock(&self.fault).take();
+        match fault {
+            Some(Fault::Panic(payload)) => std::panic::resume_unwind(payload),
+            Some(Fault::Reactor(e)) => return Some(e.into()),
+            None => {}
+        }
+        if let Some(id) = self.supervisor.failed() {
+            return Some(crate::Error::TaskFailed(id));
+        }
+        if self.shutdown.load(Ordering::SeqCst) {
+            return Some(crate::Error::Shutdown);
+        }
+        None
+    }
+}
+
+fn run(shared: Arc<Shared>, index: usize) {
+    WORKER.with(|worker| worker.set(Some((Arc::as_ptr(&shared), index))));
+    let res = std::panic::catch_unwind(AssertUnwindSafe(|| shared.work(index)));
+    if let Err(payload) = res {
+        shared.fail(Fault::Panic(payload));
+    }
+    WORKER.with(|worker| worker.set(None));
+}
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+impl Handle {
+    pub fn reactor(&self) -> Result<reactor::Handle, crate::Error> {
+        let shared = self.shared.upgrade().ok_or(crate::Error::Shutdown)?;
+        Ok(shared.reactor.clone())
+    }
+
+    /// Runs `future` on any of the workers. If the runtime has been dropped or
+    /// shut down, the returned handle resolves to `Error::Shutdown`.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send,
+    {
+        self.spawn_checked(future, |_| None)
+    }
+
+    /// See `executor::Handle::spawn_fallible`.
+    pub fn spawn_fallible<F, T>(&self, future: F) -> JoinHandle<Result<T, crate::Error>>
+    where
+        F: Future<Output = Result<T, crate::Error>> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_checked(future, |output| output.as_ref().err())
+    }
+
+    fn spawn_checked<F>(
+        &self,
+        future: F,
+        check: fn(&F::Output) -> Option<&crate::Error>,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send,
+    {
+        let shared = match self.shared.upgrade() {
+            Some(shared)
+                if !shared.shutdown.load(Ordering::SeqCst)
+                    && shared.supervisor.failed().is_none() =>
+            {
+                shared
+            }
+            _ => return JoinHandle::shut_down(),
+        };
+        let id = TaskId(shared.next_id.fetch_add(1, Ordering::SeqCst) + 1);
+        let (task, handle) = JoinHandle::new(id, future, shared.supervisor.clone(), check);
+        shared.push(Task::new(Box::pin(task), &shared));
+        handle
+    }
+}
+
+impl MultiThread {
+    /// Starts `workers` threads.
+    pub fn new(workers: usize) -> Result<Self, crate::Error> {
+        Self::from_reactor(Reactor::new()?, workers)
+    }
+
+    /// Starts `workers` threads, sharing a `Reactor` configured by a
+    /// `reactor::Builder`.
+    pub fn from_reactor(reactor: Reactor, workers: usize) -> Result<Self, crate::Error> {
+        assert!(workers > 0, "a runtime needs at least one worker");
+        let shared = Arc::new(Shared {
+            injector: Mutex::new(VecDeque::new()),
+            queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            idle: Default::default(),
+            condvar: Condvar::new(),
+            reactor: reactor.handle(),
+            notifier: reactor.notifier(),
+            driver: Mutex::new(reactor),
+            driving: AtomicBool::new(false),
+            shutdown: AtomicBool::new(false),
+            fault: Mutex::new(None),
+            blocked: Mutex::new(vec![]),
+            supervisor: Default::default(),
+            next_id: AtomicU64::new(0),
+            tasks: Mutex::new(Slab::new()),
+        });
+        let mut runtime = Self {
+            shared,
+            workers: Vec::with_capacity(workers),
+        };
+        for index in 0..workers {
+            let shared = runtime.shared.clone();
+            let worker = std::thread::Builder::new()
+                .name(format!("dope-worker-{}", index))
+                .spawn(move || run(shared, index))?;
+            runtime.workers.push(worker);
+        }
+        Ok(runtime)
+    }
+
+    pub fn set_failure_policy(&self, policy: FailurePolicy) {
+        self.shared.supervisor.set_policy(policy);
+    }
+
+    pub fn handle(&self) -> Handle {
+        Handle {
+            shared: Arc::downgrade(&self.shared),
+        }
+    }
+
+    /// Runs `future` on the current thread, while the workers run the
+    /// spawned tasks. Returns early if the runtime stops, as it does on a
+    /// failure under `FailurePolicy::shutdown_on_failure`.
+    pub fn block_on<F>(&self, future: F) -> Result<F::Output, crate::Error>
+    where
+        F: Future,
+    {
+        let mut future = std::pin::pin!(future);
+        let thread = std::thread::current();
+        lock(&self.shared.blocked).push(thread.clone());
+        let waker = Waker::from(Arc::new(Unpark(thread.clone())));
+        let mut cx = Context::from_waker(&waker);
+
+        let res = loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                break Ok(output);
+            }
+            if let Some(e) = self.shared.stopped() {
+                break Err(e);
+            }
+            std::thread::park();
+        };
+        lock(&self.shared.blocked).retain(|blocked| blocked.id() != thread.id());
+        res
+    }
+}
+
+impl Drop for MultiThread {
+    fn drop(&mut self) {
+        self.shared.stop();
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                log::error!("worker panicked");
+            }
+        }
+        // Drop the tasks while the reactor is still there, both the queued
+        // ones and those only its wakers refer to.
+        let tasks = lock(&self.shared.tasks)
+            .drain()
+            .filter_map(|task| task.upgrade())
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.cancel();
+        }
+        let queues = std::iter::once(&self.shared.injector).chain(&self.shared.queues);
+        for queue in queues {
+            lock(queue).clear();
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::task::{Context, Wake, Waker};
+
+use crate::executor::multi_thread::Shared;
+use crate::executor::scheduler::State;
+
+pub(super) type SendTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A task which may be polled by any worker, one at a time.
+pub(super) struct Task {
+    // The entry of the task in the tasks of the runtime.
+    key: usize,
+    state: State,
+    // Emptied once the task has completed.
+    future: Mutex<Option<SendTask>>,
+    runtime: Weak<Shared>,
+}
+
+impl Task {
+    /// Creates a task which is about to be queued, and keeps track of it until
+    /// it is dropped.
+    pub(super) fn new(future: SendTask, runtime: &Arc<Shared>) -> Arc<Self> {
+        let mut tasks = runtime.tasks.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = tasks.vacant_entry();
+        let task = Arc::new(Self {
+            key: entry.key(),
+            state: State::new(),
+            future: Mutex::new(Some(future)),
+            runtime: Arc::downgrade(runtime),
+        });
+        entry.insert(Arc::downgrade(&task));
+        task
+    }
+
+    /// Polls the task, and queues it again if it has been woken meanwhile.
+    pub(super) fn run(self: Arc<Self>, runtime: &Shared) {
+        self.state.start();
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = self.future.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(inner) = future.as_mut() {
+            if inner.as_mut().poll(&mut cx).is_ready() {
+                // A completed task stays running, so it is never queued again.
+                *future = None;
+                return;
+            }
+        }
+        drop(future);
+        if self.state.stop() {
+            runtime.push(self);
+        }
+    }
+
+    /// Drops the future, if the task is being dropped along with the runtime.
+    pub(super) fn cancel(&self) {
+        let future = self
+            .future
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        drop(future);
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        let runtime = match self.runtime.upgrade() {
+            Some(runtime) => runtime,
+            None => return,
+        };
+        let mut tasks = runtime.tasks.lock().unwrap_or_else(PoisonError::into_inner);
+        // The entry is gone if the runtime has cancelled the tasks, and may
+        // belong to another task since.
+        let registered = tasks
+            .get(self.key)
+            .is_some_and(|task| std::ptr::eq(task.as_ptr(), self));
+        if registered {
+            tasks.remove(self.key);
+        }
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        if self.state.wake() {
+            if let Some(runtime) = self.runtime.upgrade() {
+                runtime.push(self);
+            }
+        }
+    }
+}
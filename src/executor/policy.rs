@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::executor::TaskId;
+
+type Hook = Arc<dyn Fn(TaskId, &crate::Error) + Send + Sync>;
+
+/// What the executor does when a task fails, either by panicking or by
+/// returning an error from a task spawned with `Handle::spawn_fallible`.
+pub struct FailurePolicy {
+    hook: Option<Hook>,
+    catch_panics: bool,
+    shutdown: bool,
+}
+
+impl FailurePolicy {
+    /// Logs failures, catches panics, and keeps running.
+    pub fn new() -> Self {
+        Self {
+            hook: None,
+            catch_panics: true,
+            shutdown: false,
+        }
+    }
+
+    /// Calls `hook` with every failure, instead of logging it. The hook runs
+    /// on the thread of the failed task.
+    pub fn hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(TaskId, &crate::Error) + Send + Sync + 'static,
+    {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Turns a panic of a task into `Error::Panicked`. Otherwise, the panic
+    /// unwinds through `block_on`.
+    pub fn catch_panics(mut self, catch_panics: bool) -> Self {
+        self.catch_panics = catch_panics;
+        self
+    }
+
+    /// Makes `block_on` return `Error::TaskFailed` on the first failure,
+    /// dropping the tasks which are ready to run.
+    pub fn shutdown_on_failure(mut self, shutdown: bool) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Applies the `FailurePolicy`, shared by the executor and its tasks.
+#[derive(Default)]
+pub(super) struct Supervisor {
+    policy: Mutex<FailurePolicy>,
+    failed: Mutex<Option<TaskId>>,
+}
+
+impl Supervisor {
+    fn policy(&self) -> MutexGuard<'_, FailurePolicy> {
+        self.policy.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn set_policy(&self, policy: FailurePolicy) {
+        *self.policy() = policy;
+    }
+
+    pub fn catch_panics(&self) -> bool {
+        self.policy().catch_panics
+    }
+
+    pub fn report(&self, id: TaskId, error: &crate::Error) {
+        // The hook runs unlocked, as it may set the policy or fail a task.
+        let (hook, shutdown) = {
+            let policy = self.policy();
+            (policy.hook.clone(), policy.shutdown)
+        };
+        match hook {
+            Some(hook) => hook(id, error),
+            None => log::error!("task {:?} failed: {}", id, error),
+        }
+        if shutdown {
+            let mut failed = self.failed.lock().unwrap_or_else(PoisonError::into_inner);
+            failed.get_or_insert(id);
+        }
+    }
+
+    /// The first task which failed, if the executor has to shut down.
+    pub fn failed(&self) -> Option<TaskId> {
+        *self.failed.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,73 @@
+use std::os::unix::io::RawFd;
+
+use crate::executor::reactor::notifier::Pipe;
+use crate::executor::reactor::{Completion, Events, Key, Notifier, Op};
+
+/// An event notification mechanism driven by the `Reactor`.
+///
+/// Every registration carries a `Key`, and `poll` reports the keys of the
+/// registrations that became ready. Completion-based backends additionally
+/// accept `Op`s, whose keys are reported once they complete.
+///
+/// The reactor may be driven by any thread of a `MultiThread` runtime, so the
+/// backend has to be `Send`.
+pub trait Backend: Send {
+    /// Reports `key` whenever `fd` is readable. Write interest in the same fd,
+    /// if any, is kept along with its own key.
+    fn add_fd_read(&mut self, fd: RawFd, key: Key) -> std::io::Result<()>;
+
+    /// Reports `key` once `fd` becomes writable, keeping read interest in the
+    /// same fd. Called again to wait for the next writability.
+    fn add_fd_write(&mut self, fd: RawFd, key: Key) -> std::io::Result<()>;
+
+    /// Removes both read and write interest in `fd`.
+    fn remove_fd(&mut self, fd: RawFd) -> std::io::Result<()>;
+
+    fn add_timer(
+        &mut self,
+        duration: chrono::Duration,
+        key: Key,
+        repeat: bool,
+    ) -> std::io::Result<()>;
+
+    /// Stops a timer added with `key`, which may have fired already. Its key
+    /// must not be reported afterwards.
+    fn remove_timer(&mut self, key: Key) -> std::io::Result<()>;
+
+    fn add_signal(&mut self, signal: i32, key: Key) -> std::io::Result<()>;
+
+    /// Blocks until any registration is ready or `timeout` elapses, and fills
+    /// `events` with up to `events.capacity()` keys. `None` blocks without a
+    /// limit.
+    fn poll(
+        &mut self,
+        events: &mut Events,
+        timeout: Option<chrono::Duration>,
+    ) -> std::io::Result<()>;
+
+    /// Creates a notification source reporting `key`. Defaults to a self-pipe.
+    fn notifier(&mut self, key: Key) -> std::io::Result<Notifier> {
+        let pipe = Pipe::new()?;
+        self.add_fd_read(pipe.reader(), key)?;
+        Ok(Notifier::new(pipe))
+    }
+
+    /// Whether `submit` starts ops, as it does on completion-based backends.
+    /// Asked once, when the reactor is built.
+    fn supports_ops(&self) -> bool {
+        false
+    }
+
+    /// Starts `op`, handing it back if the backend is readiness-based.
+    fn submit(&mut self, op: Op, _key: Key) -> Result<(), Op> {
+        Err(op)
+    }
+
+    /// Takes the outcome of an `Op` whose key has been reported by `poll`.
+    fn take_completion(&mut self, _key: Key) -> Option<Completion> {
+        None
+    }
+
+    /// Cancels an `Op`. Its key must not be reported afterwards.
+    fn cancel(&mut self, _key: Key) {}
+}
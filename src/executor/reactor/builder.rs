@@ -0,0 +1,56 @@
+use crate::executor::reactor::{sys, Backend, Error, Reactor};
+
+const EVENT_CAPACITY: usize = 16;
+const MAX_EVENT_CAPACITY: usize = 1024;
+
+pub struct Builder {
+    backend: Option<Box<dyn Backend>>,
+    event_capacity: usize,
+    max_event_capacity: usize,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            event_capacity: EVENT_CAPACITY,
+            max_event_capacity: MAX_EVENT_CAPACITY,
+        }
+    }
+
+    /// Uses `backend` instead of the default one of the platform.
+    pub fn backend<B: Backend + 'static>(mut self, backend: B) -> Self {
+        self.backend = Some(Box::new(backend));
+        self
+    }
+
+    /// The number of events fetched per poll at first. It doubles whenever a
+    /// poll fills the batch, up to `max_event_capacity`.
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = std::cmp::max(capacity, 1);
+        self
+    }
+
+    pub fn max_event_capacity(mut self, capacity: usize) -> Self {
+        self.max_event_capacity = capacity;
+        self
+    }
+
+    pub fn build(self) -> Result<Reactor, Error> {
+        let backend = match self.backend {
+            Some(backend) => backend,
+            None => Box::new(sys::Selector::new()?),
+        };
+        Reactor::build(
+            backend,
+            self.event_capacity,
+            std::cmp::max(self.max_event_capacity, self.event_capacity),
+        )
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,11 +1,5 @@
 use std::task::Waker;
 
-#[derive(Debug, Fail)]
-pub enum Error {
-    #[fail(display = "no waker")]
-    NoWaker,
-}
-
 #[derive(PartialEq)]
 pub enum WakerOption {
     NeedWaker,
@@ -27,16 +21,16 @@ impl Dispatcher {
         }
     }
 
-    pub fn wake(&mut self) -> Result<(), failure::Error> {
+    /// Marks the event as available, and returns the waker to be woken once
+    /// the reactor is unlocked.
+    pub fn wake(&mut self) -> Option<Waker> {
         self.available = true;
-        if let Some(waker) = self.waker.take() {
-            waker.wake();
-            Ok(())
-        } else if self.waker_option != WakerOption::NeedWaker {
-            Ok(())
-        } else {
-            Err(Error::NoWaker.into())
+        let waker = self.waker.take();
+        if waker.is_none() && self.waker_option == WakerOption::NeedWaker {
+            // The event is kept as available until the next poll.
+            log::debug!("wake: no waker");
         }
+        waker
     }
 
     pub fn consume(&mut self) -> bool {
@@ -48,16 +42,15 @@ impl Dispatcher {
         }
     }
 
-    pub fn set_waker(&mut self, waker: Waker) -> Result<(), failure::Error> {
+    pub fn set_waker(&mut self, waker: Waker) {
         // TODO: Confirm this
         if self.waker.replace(waker).is_some() {
             log::warn!("set_waker: overwriting");
         }
-        Ok(())
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Key(usize);
 
 impl Key {
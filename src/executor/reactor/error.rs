@@ -0,0 +1,51 @@
+use crate::executor::reactor::Key;
+
+#[derive(Debug)]
+pub enum Error {
+    Backend(std::io::Error),
+    /// The poll was interrupted by a signal, and should be retried.
+    Interrupted,
+    /// An event or a request referred to a key which is no longer registered.
+    StaleKey(Key),
+    /// The reactor has been dropped.
+    Gone,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Backend(e) => write!(f, "backend failure: {}", e),
+            Error::Interrupted => write!(f, "interrupted"),
+            Error::StaleKey(key) => write!(f, "stale key: {:?}", key),
+            Error::Gone => write!(f, "reactor gone"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Backend(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::Interrupted => Error::Interrupted,
+            _ => Error::Backend(e),
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Backend(e) => e,
+            Error::Interrupted => std::io::ErrorKind::Interrupted.into(),
+            e => std::io::Error::other(e),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::executor::reactor::Key;
+
+/// The keys reported by a `Backend::poll`, reused across polls.
+pub struct Events {
+    keys: Vec<Key>,
+    capacity: usize,
+}
+
+impl Events {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            keys: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// The number of events a backend should fetch at most per poll.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&mut self, key: Key) {
+        self.keys.push(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.keys.iter(),
+        }
+    }
+
+    /// Doubles the capacity up to `limit`, if the last poll filled the batch.
+    pub(super) fn grow(&mut self, limit: usize) {
+        if self.keys.len() >= self.capacity && self.capacity < limit {
+            self.capacity = std::cmp::min(self.capacity * 2, limit);
+            log::debug!("Events::grow: {}", self.capacity);
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    inner: std::slice::Iter<'a, Key>,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Key;
+
+    fn next(&mut self) -> Option<Key> {
+        self.inner.next().copied()
+    }
+}
+
+impl<'a> IntoIterator for &'a Events {
+    type Item = Key;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
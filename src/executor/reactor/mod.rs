@@ -1,33 +1,78 @@
+mod backend;
+mod builder;
 mod dispatcher;
+mod error;
+mod events;
+mod notifier;
+mod op;
 mod register;
-mod sys;
+pub mod sys;
 
-use std::cell::RefCell;
-use std::convert::TryInto;
 use std::os::unix::io::RawFd;
-use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError, Weak};
 use std::task::{Context, Poll, Waker};
-use sys::Kqueue;
 
 use slab::Slab;
 
+pub use backend::Backend;
+pub use builder::Builder;
 pub use dispatcher::{Dispatcher, Key, WakerOption};
+pub use error::Error;
+pub use events::{Events, Iter};
+pub use notifier::{Notifier, Notify};
+pub use op::{Completion, Op, Operation};
 pub use register::Register;
 
 pub struct Reactor {
-    inner: Rc<RefCell<Inner>>,
+    shared: Arc<Shared>,
+    events: Events,
+    max_event_capacity: usize,
+    // Woken once the registrations are unlocked, reused across polls.
+    wakers: Vec<Waker>,
 }
 
+/// Registers interest in the reactor, from any thread.
 #[derive(Clone)]
 pub struct Handle {
-    inner: Weak<RefCell<Inner>>,
+    shared: Weak<Shared>,
+    // Cached from `Backend::supports_ops`, to skip `submit` without locking.
+    ops: bool,
+}
+
+struct Shared {
+    inner: Mutex<Inner>,
+    notifier: (Key, Notifier),
+    // The number of threads waiting for `inner`, while `poll` holds it.
+    waiting: AtomicUsize,
+    ops: bool,
 }
 
 struct Inner {
-    kqueue: Kqueue,
+    backend: Box<dyn Backend>,
     dispatchers: Slab<Dispatcher>,
 }
 
+impl Shared {
+    /// Locks the registrations, making a blocking `poll` return first if it
+    /// holds them.
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        match self.inner.try_lock() {
+            Ok(inner) => inner,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                self.waiting.fetch_add(1, Ordering::SeqCst);
+                if let Err(e) = self.notifier.1.notify() {
+                    log::error!("notify: {}", e);
+                }
+                let inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+                self.waiting.fetch_sub(1, Ordering::SeqCst);
+                inner
+            }
+        }
+    }
+}
+
 impl Inner {
     fn insert(&mut self, waker: Option<Waker>, waker_option: WakerOption) -> Key {
         let has_waker = waker.is_some();
@@ -39,15 +84,26 @@ impl Inner {
         res
     }
 
-    fn set_context(&mut self, key: Key, cx: &Context<'_>) -> Result<(), failure::Error> {
+    fn set_context(&mut self, key: Key, cx: &Context<'_>) -> Result<(), Error> {
         match self.dispatchers.get_mut(key.inner()) {
             Some(dispatcher) => {
                 log::info!("set_waker: {:?}", key);
-                dispatcher.set_waker(cx.waker().clone())?;
+                dispatcher.set_waker(cx.waker().clone());
+                Ok(())
             }
-            None => unreachable!(),
+            None => Err(Error::StaleKey(key)),
         }
-        Ok(())
+    }
+
+    /// Consumes an event of `key` reported since the last call, or waits for
+    /// the next one. Returns whether there was one.
+    fn rearm(&mut self, cx: &Context<'_>, key: Key) -> Result<bool, Error> {
+        let dispatcher = self.get_mut(key).ok_or(Error::StaleKey(key))?;
+        if dispatcher.consume() {
+            return Ok(true);
+        }
+        dispatcher.set_waker(cx.waker().clone());
+        Ok(false)
     }
 
     fn get_mut(&mut self, key: Key) -> Option<&mut Dispatcher> {
@@ -60,128 +116,295 @@ impl Inner {
 }
 
 impl Reactor {
-    pub fn new() -> Result<Self, failure::Error> {
+    pub fn new() -> Result<Self, Error> {
+        Builder::new().build()
+    }
+
+    pub fn with_backend<B: Backend + 'static>(backend: B) -> Result<Self, Error> {
+        Builder::new().backend(backend).build()
+    }
+
+    fn build(
+        mut backend: Box<dyn Backend>,
+        event_capacity: usize,
+        max_event_capacity: usize,
+    ) -> Result<Self, Error> {
+        let mut dispatchers = Slab::new();
+        let key = Key::from(dispatchers.insert(Dispatcher::new(None, WakerOption::None)));
+        let notifier = backend.notifier(key)?;
+        let ops = backend.supports_ops();
         Ok(Self {
-            inner: Rc::new(RefCell::new(Inner {
-                kqueue: Kqueue::new()?,
-                dispatchers: Slab::new(),
-            })),
+            shared: Arc::new(Shared {
+                inner: Mutex::new(Inner {
+                    backend,
+                    dispatchers,
+                }),
+                notifier: (key, notifier),
+                waiting: AtomicUsize::new(0),
+                ops,
+            }),
+            events: Events::with_capacity(event_capacity),
+            max_event_capacity,
+            wakers: Vec::new(),
         })
     }
 
-    pub fn poll(&mut self) -> Result<(), failure::Error> {
-        log::info!("Reactor::poll (maybe block)");
-        let polled = self.inner.borrow_mut().kqueue.poll();
-        log::info!("polled: {:?}", polled);
-        for key in polled {
-            if let Some(dispatcher) = self.inner.borrow_mut().get_mut(key) {
-                dispatcher.wake()?;
-            } else {
-                panic!()
+    /// Waits for events for up to `timeout`, or without a limit if `None`, and
+    /// wakes the tasks interested in them.
+    ///
+    /// Events for keys which are no longer registered do not stop the others
+    /// from being dispatched, and are reported as `Error::StaleKey` afterwards.
+    pub fn poll(&mut self, timeout: Option<chrono::Duration>) -> Result<(), Error> {
+        log::info!("Reactor::poll (timeout: {:?})", timeout);
+        self.events.clear();
+        let mut stale = None;
+        {
+            let mut inner = self
+                .shared
+                .inner
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            // Another thread is about to register something, so it goes first.
+            let timeout = match self.shared.waiting.load(Ordering::SeqCst) {
+                0 => timeout,
+                _ => Some(chrono::Duration::zero()),
+            };
+            inner.backend.poll(&mut self.events, timeout)?;
+            log::info!("polled: {} events", self.events.len());
+            for key in &self.events {
+                if key == self.shared.notifier.0 {
+                    self.shared.notifier.1.reset()?;
+                    continue;
+                }
+                if let Some(dispatcher) = inner.get_mut(key) {
+                    self.wakers.extend(dispatcher.wake());
+                } else {
+                    log::warn!("poll: stale key {:?}", key);
+                    stale.get_or_insert(key);
+                }
             }
         }
+        // Woken tasks may register again right away.
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+        self.events.grow(self.max_event_capacity);
 
-        Ok(())
+        match stale {
+            Some(key) => Err(Error::StaleKey(key)),
+            None => Ok(()),
+        }
+    }
+
+    /// Handles the events which are already pending, without blocking.
+    pub fn turn(&mut self) -> Result<(), Error> {
+        self.poll(Some(chrono::Duration::zero()))
+    }
+
+    /// Returns a `Notifier`, which makes a blocking `poll` return when
+    /// triggered from any thread.
+    pub fn notifier(&self) -> Notifier {
+        self.shared.notifier.1.clone()
     }
 
     pub(super) fn handle(&self) -> Handle {
         Handle {
-            inner: Rc::downgrade(&self.inner),
+            shared: Arc::downgrade(&self.shared),
+            ops: self.shared.ops,
         }
     }
 }
 
 impl Handle {
-    pub fn poll_elapsed(&self, cx: &Context<'_>, key: Key) -> Poll<Result<(), failure::Error>> {
+    /// Returns a `Notifier`, which makes the event loop run another iteration
+    /// when triggered from any thread.
+    pub fn notifier(&self) -> Result<Notifier, Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        Ok(shared.notifier.1.clone())
+    }
+
+    pub fn poll_elapsed(&self, cx: &Context<'_>, key: Key) -> Poll<Result<(), Error>> {
         log::info!("poll_elapsed");
-        if let Some(inner) = self.inner.upgrade() {
-            if let Some(dispatcher) = inner.borrow_mut().get_mut(key) {
+        let shared = match self.shared.upgrade() {
+            Some(shared) => shared,
+            None => return Poll::Ready(Err(Error::Gone)),
+        };
+        let mut borrowed = shared.lock();
+        match borrowed.get_mut(key) {
+            Some(dispatcher) => {
                 if dispatcher.consume() {
                     return Poll::Ready(Ok(()));
-                } else {
-                    dispatcher.set_waker(cx.waker().clone())?;
                 }
-            } else {
-                unreachable!()
+                dispatcher.set_waker(cx.waker().clone());
+                Poll::Pending
             }
+            None => Poll::Ready(Err(Error::StaleKey(key))),
         }
-        Poll::Pending
     }
 
-    pub fn add_signal(&self, signal: i32) -> Key {
-        if let Some(inner) = self.inner.upgrade() {
-            let mut borrowed = inner.borrow_mut();
-            let key = borrowed.insert(None, WakerOption::NeedWaker);
-            borrowed.kqueue.add_signal(signal, key).unwrap();
-            key
-        } else {
-            unreachable!()
+    pub fn add_signal(&self, signal: i32) -> Result<Key, Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let mut borrowed = shared.lock();
+        let key = borrowed.insert(None, WakerOption::NeedWaker);
+        if let Err(e) = borrowed.backend.add_signal(signal, key) {
+            borrowed.remove(key);
+            return Err(e.into());
         }
+        Ok(key)
     }
 
-    pub fn add_timer(&self, duration: chrono::Duration, repeat: bool) -> Key {
-        if let Some(inner) = self.inner.upgrade() {
-            let ident_offset = 0x1000;
-
-            let mut borrowed = inner.borrow_mut();
-            // TODO: Release from kqueue after use
-            let key = borrowed.insert(None, WakerOption::None);
-            borrowed
-                .kqueue
-                .add_timer(key.inner() + ident_offset, duration, key, repeat)
-                .unwrap();
-            key
-        } else {
-            unreachable!()
+    pub fn add_timer(&self, duration: chrono::Duration, repeat: bool) -> Result<Key, Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let mut borrowed = shared.lock();
+        let key = borrowed.insert(None, WakerOption::None);
+        if let Err(e) = borrowed.backend.add_timer(duration, key, repeat) {
+            borrowed.remove(key);
+            return Err(e.into());
         }
+        Ok(key)
     }
 
-    pub fn set_context(&self, key: Key, cx: &Context<'_>) -> Result<(), failure::Error> {
-        if let Some(inner) = self.inner.upgrade() {
-            inner.borrow_mut().set_context(key, cx)
-        } else {
-            unreachable!();
-        }
+    /// Releases a timer added with `add_timer`, whether it has fired or not.
+    pub fn remove_timer(&self, key: Key) -> Result<(), Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let mut borrowed = shared.lock();
+        borrowed.remove(key);
+        borrowed.backend.remove_timer(key)?;
+        Ok(())
     }
 
-    pub fn register_fd_read(&self, cx: &Context<'_>, fd: RawFd) -> Result<Key, failure::Error> {
-        // TODO: Merge with write
+    /// The number of registrations, including the notifier of the reactor.
+    pub fn registrations(&self) -> Result<usize, Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let len = shared.lock().dispatchers.len();
+        Ok(len)
+    }
+
+    pub fn set_context(&self, key: Key, cx: &Context<'_>) -> Result<(), Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let res = shared.lock().set_context(key, cx);
+        res
+    }
+
+    pub fn register_fd_read(&self, cx: &Context<'_>, fd: RawFd) -> Result<Key, Error> {
         log::warn!("register_fd_read: {:?}", fd);
-        if let Some(inner) = self.inner.upgrade() {
-            let mut borrowed = inner.borrow_mut();
-            let key = borrowed.insert(Some(cx.waker().clone()), WakerOption::NeedWaker);
-            borrowed.kqueue.add_fd_read(fd.try_into()?, key)?;
-            log::info!("ADDED(READ): key {:?}, fd {}", key, fd);
-            Ok(key)
-        } else {
-            unreachable!();
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let mut borrowed = shared.lock();
+        let key = borrowed.insert(Some(cx.waker().clone()), WakerOption::NeedWaker);
+        if let Err(e) = borrowed.backend.add_fd_read(fd, key) {
+            borrowed.remove(key);
+            return Err(e.into());
         }
+        log::info!("ADDED(READ): key {:?}, fd {}", key, fd);
+        Ok(key)
     }
 
-    pub fn register_fd_write(&self, cx: &Context<'_>, fd: RawFd) -> Result<Key, failure::Error> {
+    pub fn register_fd_write(&self, cx: &Context<'_>, fd: RawFd) -> Result<Key, Error> {
         log::warn!("register_fd_write: {:?}", fd);
-        if let Some(inner) = self.inner.upgrade() {
-            let mut borrowed = inner.borrow_mut();
-            // TODO: Does write need waker?`
-            let key = borrowed.insert(Some(cx.waker().clone()), WakerOption::None);
-            borrowed.kqueue.add_fd_write(fd.try_into()?, key)?;
-            log::info!("ADDED(WRITE): key {:?}, fd {}", key, fd);
-            Ok(key)
-        } else {
-            unreachable!();
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let mut borrowed = shared.lock();
+        let key = borrowed.insert(Some(cx.waker().clone()), WakerOption::None);
+        if let Err(e) = borrowed.backend.add_fd_write(fd, key) {
+            borrowed.remove(key);
+            return Err(e.into());
+        }
+        log::info!("ADDED(WRITE): key {:?}, fd {}", key, fd);
+        Ok(key)
+    }
+
+    /// Waits for the next readiness of `key`, registered for reading. The task
+    /// is woken right away if the fd became ready since the last wait, so a
+    /// readiness reported in between is not lost.
+    pub fn rearm_fd_read(&self, cx: &Context<'_>, key: Key) -> Result<(), Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let ready = shared.lock().rearm(cx, key)?;
+        if ready {
+            cx.waker().wake_by_ref();
         }
+        Ok(())
     }
 
-    pub fn unregister(&self, key: Option<Key>, fd: RawFd) -> Result<(), failure::Error> {
-        if let Some(inner) = self.inner.upgrade() {
-            let mut borrowed = inner.borrow_mut();
-            if let Some(key) = key {
+    /// Like `rearm_fd_read`, for `key` registered for writing. Write interest
+    /// is added to the backend again, as it may be dropped once reported.
+    pub fn rearm_fd_write(&self, cx: &Context<'_>, key: Key, fd: RawFd) -> Result<(), Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let ready = {
+            let mut borrowed = shared.lock();
+            let ready = borrowed.rearm(cx, key)?;
+            if !ready {
+                borrowed.backend.add_fd_write(fd, key)?;
+            }
+            ready
+        };
+        if ready {
+            cx.waker().wake_by_ref();
+        }
+        Ok(())
+    }
+
+    /// Removes `fd` from the backend, along with the keys registered for it.
+    pub fn unregister(&self, keys: &[Key], fd: RawFd) -> Result<(), Error> {
+        let shared = self.shared.upgrade().ok_or(Error::Gone)?;
+        let mut borrowed = shared.lock();
+        if !keys.is_empty() {
+            for &key in keys {
                 borrowed.remove(key);
-                borrowed.kqueue.remove_fd(fd.try_into()?)?;
             }
-            Ok(())
+            borrowed.backend.remove_fd(fd)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the backend is completion-based, and `submit` starts ops.
+    pub fn supports_ops(&self) -> bool {
+        self.ops
+    }
+
+    /// Submits `op` to a completion-based backend, handing it back if the
+    /// backend does not support it.
+    pub fn submit(&self, cx: &Context<'_>, op: Op) -> Result<Operation, Op> {
+        if !self.ops {
+            return Err(op);
+        }
+        if let Some(shared) = self.shared.upgrade() {
+            let mut borrowed = shared.lock();
+            let key = borrowed.insert(Some(cx.waker().clone()), WakerOption::NeedWaker);
+            match borrowed.backend.submit(op, key) {
+                Ok(()) => Ok(Operation::new(self.clone(), key)),
+                Err(op) => {
+                    borrowed.remove(key);
+                    Err(op)
+                }
+            }
         } else {
-            unreachable!();
+            Err(op)
+        }
+    }
+
+    pub fn poll_completion(&self, cx: &Context<'_>, key: Key) -> Poll<Result<Completion, Error>> {
+        let shared = match self.shared.upgrade() {
+            Some(shared) => shared,
+            None => return Poll::Ready(Err(Error::Gone)),
+        };
+        let mut borrowed = shared.lock();
+        if let Some(completion) = borrowed.backend.take_completion(key) {
+            borrowed.remove(key);
+            return Poll::Ready(Ok(completion));
+        }
+        match borrowed.get_mut(key) {
+            Some(dispatcher) => {
+                dispatcher.set_waker(cx.waker().clone());
+                Poll::Pending
+            }
+            None => Poll::Ready(Err(Error::StaleKey(key))),
+        }
+    }
+
+    pub fn cancel(&self, key: Key) {
+        if let Some(shared) = self.shared.upgrade() {
+            let mut borrowed = shared.lock();
+            borrowed.backend.cancel(key);
+            borrowed.remove(key);
         }
     }
 }
@@ -0,0 +1,84 @@
+use std::os::unix::io::RawFd;
+use std::sync::Arc;
+
+use crate::executor::reactor::sys::Fd;
+
+/// A notification source, which makes the backend report a key when triggered.
+pub trait Notify: Send + Sync {
+    fn notify(&self) -> std::io::Result<()>;
+
+    /// Called by the reactor once the key has been reported.
+    fn reset(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Makes a `Reactor::poll` return, from any thread.
+#[derive(Clone)]
+pub struct Notifier {
+    inner: Arc<dyn Notify>,
+}
+
+impl Notifier {
+    pub fn new<N: Notify + 'static>(notify: N) -> Self {
+        Self {
+            inner: Arc::new(notify),
+        }
+    }
+
+    pub fn notify(&self) -> std::io::Result<()> {
+        self.inner.notify()
+    }
+
+    pub(super) fn reset(&self) -> std::io::Result<()> {
+        self.inner.reset()
+    }
+}
+
+/// A self-pipe, for backends without a native notification source.
+pub(super) struct Pipe {
+    reader: Fd,
+    writer: Fd,
+}
+
+impl Pipe {
+    pub fn new() -> std::io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let (reader, writer) = (Fd(fds[0]), Fd(fds[1]));
+        for fd in &fds {
+            unsafe {
+                libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                let prev = libc::fcntl(*fd, libc::F_GETFL);
+                libc::fcntl(*fd, libc::F_SETFL, prev | libc::O_NONBLOCK);
+            }
+        }
+        Ok(Self { reader, writer })
+    }
+
+    pub fn reader(&self) -> RawFd {
+        self.reader.0
+    }
+}
+
+impl Notify for Pipe {
+    fn notify(&self) -> std::io::Result<()> {
+        let res = unsafe { libc::write(self.writer.0, [1u8].as_ptr() as *const _, 1) };
+        match res {
+            -1 => match std::io::Error::last_os_error() {
+                // A full pipe will be reported anyway.
+                ref e if e.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+                e => Err(e),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn reset(&self) -> std::io::Result<()> {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(self.reader.0, buf.as_mut_ptr() as *mut _, buf.len()) } > 0 {}
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use std::future::Future;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::executor::reactor;
+
+/// An operation for completion-based backends. Buffers are owned by the
+/// backend until the operation completes.
+pub enum Op {
+    Read { fd: RawFd, buf: Vec<u8> },
+    Write { fd: RawFd, buf: Vec<u8> },
+    Accept { fd: RawFd },
+}
+
+pub struct Completion {
+    /// The return value of the syscall, or a negated errno.
+    pub result: i32,
+    pub buf: Option<Vec<u8>>,
+}
+
+impl Completion {
+    pub fn result(&self) -> std::io::Result<usize> {
+        if self.result < 0 {
+            Err(std::io::Error::from_raw_os_error(-self.result))
+        } else {
+            Ok(self.result as usize)
+        }
+    }
+}
+
+/// A submitted `Op`, which is cancelled if dropped before completion.
+pub struct Operation {
+    reactor: reactor::Handle,
+    key: Option<reactor::Key>,
+}
+
+impl Operation {
+    pub(super) fn new(reactor: reactor::Handle, key: reactor::Key) -> Self {
+        Self {
+            reactor,
+            key: Some(key),
+        }
+    }
+}
+
+impl Future for Operation {
+    type Output = Result<Completion, reactor::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let key = self.key.expect("polled after completion");
+        let completion = futures::ready!(self.reactor.poll_completion(cx, key));
+        self.key = None;
+        Poll::Ready(completion)
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.reactor.cancel(key);
+        }
+    }
+}
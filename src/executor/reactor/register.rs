@@ -3,49 +3,86 @@ use std::task::Context;
 
 use crate::executor::reactor;
 
+/// Registers an fd with the reactor, and releases it when dropped.
+///
+/// Read and write interests are tracked apart, each with a key and a waker of
+/// its own, so one task may wait for the fd to be readable while another waits
+/// for it to be writable.
 pub struct Register {
     reactor: reactor::Handle,
     // Run first (None), and register later (Some).
-    key: Option<reactor::Key>,
+    read: Option<reactor::Key>,
+    write: Option<reactor::Key>,
+    fd: Option<RawFd>,
 }
 
 impl Register {
     pub fn new(reactor: reactor::Handle) -> Self {
-        Self { reactor, key: None }
+        Self {
+            reactor,
+            read: None,
+            write: None,
+            fd: None,
+        }
     }
 
     pub fn clone_reactor(&self) -> reactor::Handle {
         self.reactor.clone()
     }
 
-    pub fn register_read(&mut self, cx: &mut Context<'_>, fd: RawFd) -> Result<(), failure::Error> {
-        // TODO: Merge with write
-        match self.key {
-            Some(key) => self.reactor.set_context(key, cx),
+    /// See `reactor::Handle::supports_ops`.
+    pub fn supports_ops(&self) -> bool {
+        self.reactor.supports_ops()
+    }
+
+    /// Wakes the task once `fd` is readable.
+    pub fn register_read(&mut self, cx: &mut Context<'_>, fd: RawFd) -> Result<(), reactor::Error> {
+        match self.read {
+            Some(key) => self.reactor.rearm_fd_read(cx, key),
             None => {
                 let key = self.reactor.register_fd_read(cx, fd)?;
-                self.key.replace(key);
+                self.read.replace(key);
+                self.fd.replace(fd);
                 Ok(())
             }
         }
     }
 
+    /// Wakes the task once `fd` is writable.
     pub fn register_write(
         &mut self,
         cx: &mut Context<'_>,
         fd: RawFd,
-    ) -> Result<(), failure::Error> {
-        match self.key {
-            Some(key) => self.reactor.set_context(key, cx),
+    ) -> Result<(), reactor::Error> {
+        match self.write {
+            Some(key) => self.reactor.rearm_fd_write(cx, key, fd),
             None => {
                 let key = self.reactor.register_fd_write(cx, fd)?;
-                self.key.replace(key);
+                self.write.replace(key);
+                self.fd.replace(fd);
                 Ok(())
             }
         }
     }
 
-    pub fn unregister(&self, fd: RawFd) -> Result<(), failure::Error> {
-        self.reactor.unregister(self.key, fd)
+    pub fn unregister(&mut self, fd: RawFd) -> Result<(), reactor::Error> {
+        self.fd = None;
+        let keys: Vec<_> = self
+            .read
+            .take()
+            .into_iter()
+            .chain(self.write.take())
+            .collect();
+        self.reactor.unregister(&keys, fd)
+    }
+}
+
+impl Drop for Register {
+    fn drop(&mut self) {
+        if let Some(fd) = self.fd {
+            if let Err(e) = self.unregister(fd) {
+                log::warn!("unregister: {}", e);
+            }
+        }
     }
 }
@@ -0,0 +1,335 @@
+use crate::executor::reactor::sys::Fd;
+use crate::executor::reactor::{self, Backend, Events, Notifier, Notify};
+use std::collections::HashMap;
+use std::os::unix::io::RawFd;
+
+struct EventFd(Fd);
+
+/// Treats a would-block as done: the counter is full, so the notification is
+/// pending already, or empty, so there is nothing to reset.
+fn check(res: isize) -> std::io::Result<()> {
+    match res {
+        -1 => match std::io::Error::last_os_error() {
+            ref e if e.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+            e => Err(e),
+        },
+        _ => Ok(()),
+    }
+}
+
+impl Notify for EventFd {
+    fn notify(&self) -> std::io::Result<()> {
+        let one = 1u64;
+        check(unsafe { libc::write((self.0).0, &one as *const u64 as *const _, 8) })
+    }
+
+    fn reset(&self) -> std::io::Result<()> {
+        let mut count = 0u64;
+        check(unsafe { libc::read((self.0).0, &mut count as *mut u64 as *mut _, 8) })
+    }
+}
+
+/// Marks the `udata` of registered fds, which is the fd itself, apart from the
+/// keys of backend-owned descriptors.
+const FD_TAG: u64 = 1 << 63;
+
+/// The interests in an fd. epoll keeps a single entry per fd, so they are
+/// merged, and events are dispatched to the keys by their kind.
+///
+/// Both are level-triggered. Write interest is dropped once reported, so that
+/// a writable socket does not keep firing, and is added again by
+/// `add_fd_write` to wait for the next writability. An fd left without
+/// interest is taken out of the epoll set, which would otherwise keep
+/// reporting a hang-up to no one, and added back along with its next interest.
+#[derive(Clone, Copy, Default)]
+struct Interest {
+    events: u32,
+    read: Option<reactor::Key>,
+    write: Option<reactor::Key>,
+}
+
+/// Descriptors created by the backend itself, which must be drained when they
+/// become readable.
+enum Source {
+    Timer { fd: RawFd, repeat: bool },
+    Signal { fd: RawFd },
+}
+
+pub struct Epoll {
+    ep: RawFd,
+    events: Vec<libc::epoll_event>,
+    interests: HashMap<RawFd, Interest>,
+    sources: HashMap<usize, Source>,
+}
+
+impl Epoll {
+    pub fn new() -> std::io::Result<Self> {
+        let res = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self {
+            ep: res,
+            events: vec![],
+            interests: HashMap::new(),
+            sources: HashMap::new(),
+        })
+    }
+
+    fn manage_event(&mut self, op: i32, fd: RawFd, events: u32, udata: u64) -> std::io::Result<()> {
+        let mut event = libc::epoll_event { events, u64: udata };
+        let res = unsafe { libc::epoll_ctl(self.ep, op, fd, &mut event) };
+        if res == -1 {
+            log::error!("epoll_ctl");
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_interest(&mut self, fd: RawFd, events: u32, key: reactor::Key) -> std::io::Result<()> {
+        let (op, mut interest) = match self.interests.get(&fd) {
+            Some(interest) if interest.events != 0 => (libc::EPOLL_CTL_MOD, *interest),
+            Some(interest) => (libc::EPOLL_CTL_ADD, *interest),
+            None => (libc::EPOLL_CTL_ADD, Interest::default()),
+        };
+        interest.events |= events;
+        if events & libc::EPOLLOUT as u32 != 0 {
+            interest.write = Some(key);
+        } else {
+            interest.read = Some(key);
+        }
+        self.manage_event(op, fd, interest.events, FD_TAG | fd as u64)?;
+        self.interests.insert(fd, interest);
+        Ok(())
+    }
+
+    /// Reports the keys interested in the events of `fd`.
+    fn dispatch(&mut self, fd: RawFd, ready: u32, events: &mut Events) {
+        let interest = match self.interests.get_mut(&fd) {
+            Some(interest) => interest,
+            None => return,
+        };
+        let closed = (libc::EPOLLHUP | libc::EPOLLERR) as u32;
+        if ready & (libc::EPOLLIN as u32 | closed) != 0 {
+            if let Some(key) = interest.read {
+                events.push(key);
+            }
+        }
+        let out = libc::EPOLLOUT as u32;
+        if ready & (out | closed) != 0 && interest.events & out != 0 {
+            if let Some(key) = interest.write {
+                events.push(key);
+            }
+            interest.events &= !out;
+            let mask = interest.events;
+            let op = if mask == 0 {
+                libc::EPOLL_CTL_DEL
+            } else {
+                libc::EPOLL_CTL_MOD
+            };
+            if let Err(e) = self.manage_event(op, fd, mask, FD_TAG | fd as u64) {
+                log::warn!("drop write interest of fd {}: {}", fd, e);
+            }
+        }
+    }
+
+    fn add_source(&mut self, fd: RawFd, source: Source, key: reactor::Key) -> std::io::Result<()> {
+        if let Err(e) = self.manage_event(
+            libc::EPOLL_CTL_ADD,
+            fd,
+            libc::EPOLLIN as u32,
+            key.inner() as u64,
+        ) {
+            unsafe { libc::close(fd) };
+            return Err(e);
+        }
+        self.sources.insert(key.inner(), source);
+        Ok(())
+    }
+
+    fn fetch_events(
+        &mut self,
+        capacity: usize,
+        timeout: Option<chrono::Duration>,
+    ) -> std::io::Result<()> {
+        // Round up, so that a pending timer is not polled too early.
+        let timeout = timeout.map_or(-1, |timeout| {
+            let nanos = std::cmp::max(timeout.num_nanoseconds().unwrap_or(i64::MAX), 0);
+            std::cmp::min((nanos + 999_999) / 1_000_000, i64::from(i32::MAX)) as i32
+        });
+        self.events.clear();
+        self.events.reserve_exact(capacity);
+        unsafe {
+            let res = libc::epoll_wait(self.ep, self.events.as_mut_ptr(), capacity as i32, timeout);
+            if res == -1 {
+                return Err(std::io::Error::last_os_error());
+            } else {
+                self.events.set_len(res as usize);
+            }
+        }
+        Ok(())
+    }
+
+    /// Consumes the readiness of a backend-owned descriptor, so that it does
+    /// not keep firing.
+    fn drain(&mut self, key: usize) {
+        match self.sources.get(&key) {
+            Some(Source::Timer { fd, repeat }) => {
+                let (fd, repeat) = (*fd, *repeat);
+                let mut expirations = 0u64;
+                unsafe {
+                    libc::read(fd, &mut expirations as *mut u64 as *mut _, 8);
+                }
+                if !repeat {
+                    let _ = self.manage_event(libc::EPOLL_CTL_DEL, fd, 0, 0);
+                    unsafe { libc::close(fd) };
+                    self.sources.remove(&key);
+                }
+            }
+            Some(Source::Signal { fd }) => {
+                let size = std::mem::size_of::<libc::signalfd_siginfo>();
+                let mut info = unsafe { std::mem::zeroed::<libc::signalfd_siginfo>() };
+                while unsafe { libc::read(*fd, &mut info as *mut _ as *mut _, size) } > 0 {}
+            }
+            None => {}
+        }
+    }
+}
+
+impl Backend for Epoll {
+    fn add_fd_read(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
+        self.add_interest(fd, libc::EPOLLIN as u32, key)
+    }
+
+    fn add_fd_write(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
+        self.add_interest(fd, libc::EPOLLOUT as u32, key)
+    }
+
+    fn remove_fd(&mut self, fd: RawFd) -> std::io::Result<()> {
+        // Forgotten even if the fd is closed already, so that a new fd with the
+        // same number starts afresh.
+        match self.interests.remove(&fd) {
+            // Out of the epoll set already.
+            Some(interest) if interest.events == 0 => Ok(()),
+            _ => self.manage_event(libc::EPOLL_CTL_DEL, fd, 0, 0),
+        }
+    }
+
+    fn add_timer(
+        &mut self,
+        duration: chrono::Duration,
+        key: reactor::Key,
+        repeat: bool,
+    ) -> std::io::Result<()> {
+        let fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_NONBLOCK | libc::TFD_CLOEXEC,
+            )
+        };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        // A zeroed `it_value` disarms the timer, so fire after 1ns instead.
+        let nanos = std::cmp::max(duration.num_nanoseconds().unwrap_or(i64::MAX), 1);
+        let value = libc::timespec {
+            tv_sec: (nanos / 1_000_000_000) as libc::time_t,
+            tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+        };
+        let spec = libc::itimerspec {
+            it_interval: if repeat {
+                value
+            } else {
+                libc::timespec {
+                    tv_sec: 0,
+                    tv_nsec: 0,
+                }
+            },
+            it_value: value,
+        };
+        if unsafe { libc::timerfd_settime(fd, 0, &spec, std::ptr::null_mut()) } == -1 {
+            let err = std::io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+        self.add_source(fd, Source::Timer { fd, repeat }, key)
+    }
+
+    fn remove_timer(&mut self, key: reactor::Key) -> std::io::Result<()> {
+        // A timer which has fired once is closed already.
+        if let Some(Source::Timer { fd, .. }) = self.sources.get(&key.inner()) {
+            let fd = *fd;
+            self.sources.remove(&key.inner());
+            let res = self.manage_event(libc::EPOLL_CTL_DEL, fd, 0, 0);
+            unsafe { libc::close(fd) };
+            res?;
+        }
+        Ok(())
+    }
+
+    fn add_signal(&mut self, signal: i32, key: reactor::Key) -> std::io::Result<()> {
+        let fd = unsafe {
+            let mut mask = std::mem::zeroed::<libc::sigset_t>();
+            libc::sigemptyset(&mut mask);
+            libc::sigaddset(&mut mask, signal);
+            // The signal must be blocked, otherwise its default disposition runs
+            // instead of it being queued to the signalfd.
+            libc::pthread_sigmask(libc::SIG_BLOCK, &mask, std::ptr::null_mut());
+            libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC)
+        };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        self.add_source(fd, Source::Signal { fd }, key)
+    }
+
+    fn notifier(&mut self, key: reactor::Key) -> std::io::Result<Notifier> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let eventfd = EventFd(Fd(fd));
+        self.add_fd_read(fd, key)?;
+        Ok(Notifier::new(eventfd))
+    }
+
+    fn poll(
+        &mut self,
+        events: &mut Events,
+        timeout: Option<chrono::Duration>,
+    ) -> std::io::Result<()> {
+        self.fetch_events(events.capacity(), timeout)?;
+        for i in 0..self.events.len() {
+            let (ready, udata) = (self.events[i].events, self.events[i].u64);
+            debug!(
+                "polling: {} in: {} out: {} udata: {}",
+                ready & libc::EPOLLHUP as u32,
+                ready & libc::EPOLLIN as u32,
+                ready & libc::EPOLLOUT as u32,
+                udata,
+            );
+            if udata & FD_TAG != 0 {
+                self.dispatch((udata & !FD_TAG) as RawFd, ready, events);
+                continue;
+            }
+            self.drain(udata as usize);
+            events.push(reactor::Key::from(udata as usize));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Epoll {
+    fn drop(&mut self) {
+        for source in self.sources.values() {
+            match source {
+                Source::Timer { fd, .. } | Source::Signal { fd } => unsafe {
+                    libc::close(*fd);
+                },
+            }
+        }
+        unsafe { libc::close(self.ep) };
+    }
+}
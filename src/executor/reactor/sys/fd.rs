@@ -0,0 +1,10 @@
+use std::os::unix::io::RawFd;
+
+/// An owned file descriptor, closed on drop.
+pub(in crate::executor::reactor) struct Fd(pub RawFd);
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
@@ -1,30 +1,85 @@
 mod kevent;
 
-use crate::executor::reactor;
+use crate::executor::reactor::sys::Fd;
+use crate::executor::reactor::{self, Backend, Events, Notifier, Notify};
 use std::os::unix::io::RawFd;
+use std::sync::Arc;
 
-#[derive(Debug, Fail)]
-pub(in crate::executor::reactor) enum Error {
-    #[fail(display = "kqueue returned -1")]
-    Kqueue,
-    #[fail(display = "kevent returned -1")]
-    Kevent,
+/// Timers share the ident space of kevent, so keep them apart from fds.
+const TIMER_IDENT_OFFSET: usize = 0x1000;
+/// `EVFILT_USER` has an ident space of its own.
+const NOTIFY_IDENT: usize = 0;
+
+pub struct Kqueue {
+    kq: Arc<Fd>,
+    events: Vec<libc::kevent>,
 }
 
-pub(in crate::executor::reactor) struct Kqueue {
+// `udata` of the buffered events only ever carries a key, never a pointer.
+unsafe impl Send for Kqueue {}
+
+fn kevent(
     kq: RawFd,
-    events: Vec<libc::kevent>,
+    ident: usize,
+    filter: i16,
+    flags: u16,
+    fflags: u32,
+    data: isize,
+    udata: usize,
+) -> std::io::Result<()> {
+    let changes = [libc::kevent {
+        ident: ident as libc::uintptr_t,
+        filter,
+        flags,
+        fflags,
+        data,
+        udata: udata as *mut _,
+    }];
+    let res = unsafe {
+        libc::kevent(
+            kq,
+            changes.as_ptr(),
+            changes.len() as i32,
+            ::std::ptr::null_mut(),
+            0,
+            std::ptr::null(),
+        )
+    };
+    if res == -1 {
+        log::error!("kevent");
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+struct UserEvent {
+    kq: Arc<Fd>,
+}
+
+impl Notify for UserEvent {
+    fn notify(&self) -> std::io::Result<()> {
+        kevent(
+            self.kq.0,
+            NOTIFY_IDENT,
+            libc::EVFILT_USER,
+            0,
+            libc::NOTE_TRIGGER,
+            0,
+            0,
+        )
+    }
 }
 
 impl Kqueue {
-    pub fn new() -> Result<Self, Error> {
+    pub fn new() -> std::io::Result<Self> {
         let res = unsafe { libc::kqueue() };
         if res == -1 {
-            return Err(Error::Kqueue);
+            return Err(std::io::Error::last_os_error());
         }
         Ok(Self {
-            kq: res,
-            events: Vec::with_capacity(16),
+            kq: Arc::new(Fd(res)),
+            events: vec![],
         })
     }
 
@@ -36,36 +91,49 @@ impl Kqueue {
         fflags: u32,
         data: isize,
         udata: usize,
-    ) -> Result<(), failure::Error> {
-        let changes = vec![libc::kevent {
-            ident: ident as libc::uintptr_t,
-            filter,
-            flags,
-            fflags,
-            data,
-            udata: udata as *mut _,
-        }];
-        let res = unsafe {
-            libc::kevent(
-                self.kq,
-                changes.as_ptr(),
-                changes.len() as i32,
-                ::std::ptr::null_mut(),
-                0,
+    ) -> std::io::Result<()> {
+        kevent(self.kq.0, ident, filter, flags, fflags, data, udata)
+    }
+
+    fn fetch_events(
+        &mut self,
+        capacity: usize,
+        timeout: Option<chrono::Duration>,
+    ) -> std::io::Result<()> {
+        let timeout = timeout.map(|timeout| {
+            let nanos = std::cmp::max(timeout.num_nanoseconds().unwrap_or(i64::MAX), 0);
+            libc::timespec {
+                tv_sec: (nanos / 1_000_000_000) as libc::time_t,
+                tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+            }
+        });
+        self.events.clear();
+        self.events.reserve_exact(capacity);
+        unsafe {
+            let res = libc::kevent(
+                self.kq.0,
                 std::ptr::null(),
-            )
-        };
-        if res == -1 {
-            log::error!("kevent");
-            Err(Error::Kevent.into())
-        } else {
-            Ok(())
+                0,
+                self.events.as_mut_ptr(),
+                capacity as i32,
+                timeout
+                    .as_ref()
+                    .map_or(std::ptr::null(), |timeout| timeout as *const _),
+            );
+            if res == -1 {
+                return Err(std::io::Error::last_os_error());
+            } else {
+                self.events.set_len(res as usize);
+            }
         }
+        Ok(())
     }
+}
 
-    pub fn add_fd_read(&mut self, fd: usize, key: reactor::Key) -> Result<(), failure::Error> {
+impl Backend for Kqueue {
+    fn add_fd_read(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
         self.manage_event(
-            fd,
+            fd as usize,
             libc::EVFILT_READ,
             libc::EV_ADD | libc::EV_ENABLE,
             0,
@@ -74,9 +142,9 @@ impl Kqueue {
         )
     }
 
-    pub fn add_fd_write(&mut self, fd: usize, key: reactor::Key) -> Result<(), failure::Error> {
+    fn add_fd_write(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
         self.manage_event(
-            fd,
+            fd as usize,
             libc::EVFILT_WRITE,
             libc::EV_ADD | libc::EV_ENABLE | libc::EV_CLEAR,
             0,
@@ -85,26 +153,25 @@ impl Kqueue {
         )
     }
 
-    pub fn remove_fd(&mut self, fd: usize) -> Result<(), failure::Error> {
-        self.manage_event(
-            fd,
-            libc::EVFILT_READ,
-            libc::EV_DELETE | libc::EV_DISABLE,
-            0,
-            0,
-            0,
-        )
+    fn remove_fd(&mut self, fd: RawFd) -> std::io::Result<()> {
+        // Either filter may not have been added.
+        for filter in [libc::EVFILT_READ, libc::EVFILT_WRITE] {
+            match self.manage_event(fd as usize, filter, libc::EV_DELETE, 0, 0, 0) {
+                Err(e) if e.raw_os_error() != Some(libc::ENOENT) => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
     }
 
-    pub fn add_timer(
+    fn add_timer(
         &mut self,
-        ident: usize,
         duration: chrono::Duration,
         key: reactor::Key,
         repeat: bool,
-    ) -> Result<(), failure::Error> {
+    ) -> std::io::Result<()> {
         self.manage_event(
-            ident,
+            key.inner() + TIMER_IDENT_OFFSET,
             libc::EVFILT_TIMER,
             libc::EV_ADD | libc::EV_ENABLE | if repeat { 0 } else { libc::EV_ONESHOT },
             0,
@@ -113,7 +180,16 @@ impl Kqueue {
         )
     }
 
-    pub fn add_signal(&mut self, signal: i32, key: reactor::Key) -> Result<(), failure::Error> {
+    fn remove_timer(&mut self, key: reactor::Key) -> std::io::Result<()> {
+        let ident = key.inner() + TIMER_IDENT_OFFSET;
+        match self.manage_event(ident, libc::EVFILT_TIMER, libc::EV_DELETE, 0, 0, 0) {
+            // A one-shot timer is gone once it has fired.
+            Err(e) if e.raw_os_error() != Some(libc::ENOENT) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn add_signal(&mut self, signal: i32, key: reactor::Key) -> std::io::Result<()> {
         self.manage_event(
             signal as usize,
             libc::EVFILT_SIGNAL,
@@ -124,45 +200,42 @@ impl Kqueue {
         )
     }
 
-    fn fetch_events(&mut self) -> Result<(), Error> {
-        unsafe {
-            let res = libc::kevent(
-                self.kq,
-                std::ptr::null(),
-                0,
-                self.events.as_mut_ptr(),
-                self.events.capacity() as i32,
-                std::ptr::null(),
-            );
-            if res == -1 {
-                return Err(Error::Kevent);
-            } else {
-                self.events.set_len(res as usize);
-            }
-        }
-        Ok(())
+    fn notifier(&mut self, key: reactor::Key) -> std::io::Result<Notifier> {
+        self.manage_event(
+            NOTIFY_IDENT,
+            libc::EVFILT_USER,
+            libc::EV_ADD | libc::EV_ENABLE | libc::EV_CLEAR,
+            0,
+            0,
+            key.inner(),
+        )?;
+        Ok(Notifier::new(UserEvent {
+            kq: self.kq.clone(),
+        }))
     }
 
-    pub fn poll(&mut self) -> Vec<reactor::Key> {
+    fn poll(
+        &mut self,
+        events: &mut Events,
+        timeout: Option<chrono::Duration>,
+    ) -> std::io::Result<()> {
         use num_traits::FromPrimitive;
 
-        self.fetch_events().unwrap();
-        self.events
-            .iter()
-            .map(|e| {
-                let filter = e.filter;
-                debug!(
-                    "polling: {} ident: {} filter: {} {:?} data: {} udata: {} fflags: {}",
-                    e.flags & libc::EV_EOF,
-                    e.ident as i32,
-                    filter,
-                    kevent::Filter::from_i16(filter as i16),
-                    e.data as i32,
-                    e.udata as i32,
-                    e.fflags as i32,
-                );
-                reactor::Key::from(e.udata as usize)
-            })
-            .collect()
+        self.fetch_events(events.capacity(), timeout)?;
+        for e in &self.events {
+            let filter = e.filter;
+            debug!(
+                "polling: {} ident: {} filter: {} {:?} data: {} udata: {} fflags: {}",
+                e.flags & libc::EV_EOF,
+                e.ident as i32,
+                filter,
+                kevent::Filter::from_i16(filter),
+                e.data as i32,
+                e.udata as i32,
+                e.fflags as i32,
+            );
+            events.push(reactor::Key::from(e.udata as usize));
+        }
+        Ok(())
     }
 }
@@ -1,3 +1,25 @@
+#[cfg(target_os = "linux")]
+mod epoll;
+mod fd;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 mod kqueue;
+mod poll;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
 
-pub(in crate::executor::reactor) use self::kqueue::Kqueue;
+#[cfg(target_os = "linux")]
+pub use self::epoll::Epoll;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub use self::kqueue::Kqueue;
+pub use self::poll::Poll;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub use self::uring::Uring;
+
+pub(in crate::executor::reactor) use self::fd::Fd;
+
+#[cfg(target_os = "linux")]
+pub(in crate::executor::reactor) use self::epoll::Epoll as Selector;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub(in crate::executor::reactor) use self::kqueue::Kqueue as Selector;
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+pub(in crate::executor::reactor) use self::poll::Poll as Selector;
@@ -0,0 +1,167 @@
+mod wheel;
+
+use crate::executor::reactor::{self, Backend, Events};
+use std::os::unix::io::RawFd;
+use std::time::Instant;
+
+use wheel::Wheel;
+
+/// A portable backend built on `poll(2)`, for platforms where neither kqueue
+/// nor epoll is available.
+///
+/// Timers are emulated with a timing wheel. As `poll(2)` is level-triggered,
+/// write interest is dropped once it has been reported, and has to be added
+/// again to be notified of the next writability. An fd left without interest
+/// is not polled at all, as a hang-up would be reported to no one.
+pub struct Poll {
+    fds: Vec<libc::pollfd>,
+    // The read and write keys of `fds`, at the same index.
+    keys: Vec<(Option<reactor::Key>, Option<reactor::Key>)>,
+    wheel: Wheel,
+}
+
+impl Poll {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            fds: vec![],
+            keys: vec![],
+            wheel: Wheel::new(),
+        })
+    }
+
+    fn add_interest(&mut self, fd: RawFd, interest: i16, key: reactor::Key) -> std::io::Result<()> {
+        let index = match self.fds.iter().position(|pollfd| pollfd.fd == fd) {
+            Some(index) => index,
+            None => {
+                self.fds.push(libc::pollfd {
+                    fd,
+                    events: 0,
+                    revents: 0,
+                });
+                self.keys.push((None, None));
+                self.fds.len() - 1
+            }
+        };
+        self.fds[index].events |= interest;
+        if interest == libc::POLLOUT {
+            self.keys[index].1 = Some(key);
+        } else {
+            self.keys[index].0 = Some(key);
+        }
+        Ok(())
+    }
+
+    fn fetch_events(&mut self, timeout: Option<chrono::Duration>) -> std::io::Result<()> {
+        let timeout = timeout.map(|timeout| timeout.to_std().unwrap_or_default());
+        let timer = self.wheel.next_timeout(Instant::now());
+        let timeout = match (timeout, timer) {
+            (Some(timeout), Some(timer)) => Some(std::cmp::min(timeout, timer)),
+            (timeout, timer) => timeout.or(timer),
+        };
+        // Round up, so that a pending timer is not polled too early.
+        let timeout = timeout.map_or(-1, |timeout| {
+            let millis = timeout.as_nanos().div_ceil(1_000_000);
+            std::cmp::min(millis, i32::MAX as u128) as i32
+        });
+
+        let res = unsafe {
+            libc::poll(
+                self.fds.as_mut_ptr(),
+                self.fds.len() as libc::nfds_t,
+                timeout,
+            )
+        };
+        if res == -1 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Backend for Poll {
+    fn add_fd_read(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
+        self.add_interest(fd, libc::POLLIN, key)
+    }
+
+    fn add_fd_write(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
+        self.add_interest(fd, libc::POLLOUT, key)
+    }
+
+    fn remove_fd(&mut self, fd: RawFd) -> std::io::Result<()> {
+        // Missing if its only interest has been reported already.
+        if let Some(index) = self.fds.iter().position(|pollfd| pollfd.fd == fd) {
+            self.fds.swap_remove(index);
+            self.keys.swap_remove(index);
+        }
+        Ok(())
+    }
+
+    fn add_timer(
+        &mut self,
+        duration: chrono::Duration,
+        key: reactor::Key,
+        repeat: bool,
+    ) -> std::io::Result<()> {
+        self.wheel
+            .insert(duration.to_std().unwrap_or_default(), key, repeat);
+        Ok(())
+    }
+
+    fn remove_timer(&mut self, key: reactor::Key) -> std::io::Result<()> {
+        self.wheel.remove(key);
+        Ok(())
+    }
+
+    fn add_signal(&mut self, _signal: i32, _key: reactor::Key) -> std::io::Result<()> {
+        Err(std::io::Error::other(
+            "signals are not supported by the poll backend",
+        ))
+    }
+
+    fn poll(
+        &mut self,
+        events: &mut Events,
+        timeout: Option<chrono::Duration>,
+    ) -> std::io::Result<()> {
+        self.fetch_events(timeout)?;
+
+        let closed = libc::POLLHUP | libc::POLLERR;
+        let mut index = 0;
+        while index < self.fds.len() {
+            let pollfd = &mut self.fds[index];
+            let (read, write) = self.keys[index];
+            if pollfd.revents == 0 {
+                index += 1;
+                continue;
+            }
+            debug!(
+                "polling: fd: {} revents: {} keys: {:?} {:?}",
+                pollfd.fd, pollfd.revents, read, write
+            );
+            if pollfd.revents & (libc::POLLIN | closed) != 0 {
+                if let Some(key) = read {
+                    events.push(key);
+                }
+            }
+            if pollfd.revents & (libc::POLLOUT | closed) != 0 && pollfd.events & libc::POLLOUT != 0
+            {
+                pollfd.events &= !libc::POLLOUT;
+                if let Some(key) = write {
+                    events.push(key);
+                }
+            }
+            pollfd.revents = 0;
+            if pollfd.events == 0 {
+                self.fds.swap_remove(index);
+                self.keys.swap_remove(index);
+            } else {
+                index += 1;
+            }
+        }
+        for key in self.wheel.advance(Instant::now()) {
+            events.push(key);
+        }
+        Ok(())
+    }
+}
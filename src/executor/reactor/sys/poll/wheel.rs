@@ -0,0 +1,108 @@
+use std::time::{Duration, Instant};
+
+use crate::executor::reactor;
+
+const SLOTS: u64 = 256;
+const RESOLUTION: Duration = Duration::from_millis(1);
+
+struct Entry {
+    deadline: u64,
+    period: Option<u64>,
+    key: reactor::Key,
+}
+
+/// A hashed timing wheel, with deadlines measured in ticks of `RESOLUTION`.
+pub(super) struct Wheel {
+    origin: Instant,
+    tick: u64,
+    slots: Vec<Vec<Entry>>,
+}
+
+impl Wheel {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            tick: 0,
+            slots: (0..SLOTS).map(|_| vec![]).collect(),
+        }
+    }
+
+    fn ticks(duration: Duration) -> u64 {
+        let nanos = RESOLUTION.as_nanos();
+        std::cmp::max(duration.as_nanos().div_ceil(nanos), 1) as u64
+    }
+
+    fn elapsed(&self, now: Instant) -> u64 {
+        (now.saturating_duration_since(self.origin).as_nanos() / RESOLUTION.as_nanos()) as u64
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.slots[(entry.deadline % SLOTS) as usize].push(entry);
+    }
+
+    pub fn insert(&mut self, duration: Duration, key: reactor::Key, repeat: bool) {
+        let ticks = Self::ticks(duration);
+        let now = std::cmp::max(self.elapsed(Instant::now()), self.tick);
+        self.push(Entry {
+            deadline: now + ticks,
+            period: if repeat { Some(ticks) } else { None },
+            key,
+        });
+    }
+
+    pub fn remove(&mut self, key: reactor::Key) {
+        for slot in &mut self.slots {
+            slot.retain(|entry| entry.key != key);
+        }
+    }
+
+    /// Time left until the earliest deadline.
+    pub fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|entry| entry.deadline)
+            .min()
+            .map(|deadline| {
+                let at =
+                    self.origin + Duration::from_nanos(deadline * RESOLUTION.as_nanos() as u64);
+                at.saturating_duration_since(now)
+            })
+    }
+
+    /// Turns the wheel up to `now`, returning the keys of expired timers.
+    pub fn advance(&mut self, now: Instant) -> Vec<reactor::Key> {
+        let target = self.elapsed(now);
+        if target <= self.tick {
+            return vec![];
+        }
+
+        let mut expired = vec![];
+        // Visiting every slot once is enough to find all expired entries.
+        let steps = std::cmp::min(target - self.tick, SLOTS);
+        for step in 1..=steps {
+            let slot = &mut self.slots[((self.tick + step) % SLOTS) as usize];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].deadline <= target {
+                    expired.push(slot.swap_remove(i));
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.tick = target;
+
+        expired.sort_by_key(|entry| entry.deadline);
+        let keys = expired.iter().map(|entry| entry.key).collect();
+        for mut entry in expired {
+            if let Some(period) = entry.period {
+                // Skip the periods missed, like a kqueue timer does.
+                let missed = (target - entry.deadline) / period + 1;
+                entry.deadline += missed * period;
+                self.push(entry);
+            }
+        }
+        keys
+    }
+}
@@ -0,0 +1,408 @@
+use crate::executor::reactor::{self, Backend, Completion, Events, Op};
+use std::collections::HashMap;
+use std::os::unix::io::RawFd;
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+/// Completions of removals and cancellations, which are not reported.
+const IGNORED: u64 = 0;
+/// The timeout bounding a blocking `poll`.
+const POLL_TIMEOUT: u64 = u64::MAX;
+
+/// Submissions which have not completed yet, by `user_data`.
+enum Pending {
+    Poll {
+        fd: RawFd,
+        flags: u32,
+        key: reactor::Key,
+        rearm: bool,
+    },
+    Signal {
+        fd: RawFd,
+        key: reactor::Key,
+    },
+    Timer {
+        timespec: Box<types::Timespec>,
+        key: reactor::Key,
+        repeat: bool,
+    },
+    // A removed timer, whose timespec is kept until the timeout completes.
+    Removed {
+        _timespec: Box<types::Timespec>,
+    },
+    // `key` is taken once the operation is cancelled.
+    Op {
+        key: Option<reactor::Key>,
+        buf: Option<Vec<u8>>,
+    },
+}
+
+/// A completion-based backend built on io_uring.
+///
+/// Readiness is emulated with one-shot poll requests. Read interest and
+/// signals are re-armed after they fire, while write interest is dropped once
+/// reported, and has to be added again for the next writability. Repeating
+/// timers are re-armed when they fire, so they drift by the reaction time.
+pub struct Uring {
+    ring: IoUring,
+    next: u64,
+    pending: HashMap<u64, Pending>,
+    ops: HashMap<usize, u64>,
+    completions: HashMap<usize, Completion>,
+    backlog: Vec<squeue::Entry>,
+    timeout: types::Timespec,
+}
+
+fn timespec(duration: chrono::Duration) -> types::Timespec {
+    let nanos = std::cmp::max(duration.num_nanoseconds().unwrap_or(i64::MAX), 0);
+    types::Timespec::new()
+        .sec((nanos / 1_000_000_000) as u64)
+        .nsec((nanos % 1_000_000_000) as u32)
+}
+
+impl Uring {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            ring: IoUring::new(256)?,
+            next: IGNORED + 1,
+            pending: HashMap::new(),
+            ops: HashMap::new(),
+            completions: HashMap::new(),
+            backlog: vec![],
+            timeout: types::Timespec::new(),
+        })
+    }
+
+    fn push(&mut self, entry: squeue::Entry) {
+        // Safety: buffers and timespecs referred to by entries are kept in
+        // `pending` until their completion is reaped.
+        if unsafe { self.ring.submission().push(&entry) }.is_ok() {
+            return;
+        }
+        if self.ring.submit().is_err() || unsafe { self.ring.submission().push(&entry) }.is_err() {
+            self.backlog.push(entry);
+        }
+    }
+
+    fn start(&mut self, entry: squeue::Entry, pending: Pending) -> u64 {
+        let user_data = self.next;
+        self.next += 1;
+        self.push(entry.user_data(user_data));
+        self.pending.insert(user_data, pending);
+        user_data
+    }
+
+    fn start_poll(&mut self, fd: RawFd, flags: u32, key: reactor::Key, rearm: bool) {
+        let entry = opcode::PollAdd::new(types::Fd(fd), flags).build();
+        self.start(
+            entry,
+            Pending::Poll {
+                fd,
+                flags,
+                key,
+                rearm,
+            },
+        );
+    }
+
+    fn start_signal(&mut self, fd: RawFd, key: reactor::Key) {
+        let entry = opcode::PollAdd::new(types::Fd(fd), libc::POLLIN as u32).build();
+        self.start(entry, Pending::Signal { fd, key });
+    }
+
+    fn start_timer(&mut self, timespec: Box<types::Timespec>, key: reactor::Key, repeat: bool) {
+        let entry = opcode::Timeout::new(&*timespec).build();
+        self.start(
+            entry,
+            Pending::Timer {
+                timespec,
+                key,
+                repeat,
+            },
+        );
+    }
+
+    fn submit_and_wait(&mut self, timeout: Option<chrono::Duration>) -> std::io::Result<()> {
+        for entry in std::mem::take(&mut self.backlog) {
+            self.push(entry);
+        }
+        match timeout {
+            Some(timeout) if timeout <= chrono::Duration::zero() => self.ring.submit()?,
+            Some(timeout) => {
+                self.timeout = timespec(timeout);
+                let entry = opcode::Timeout::new(&self.timeout).build();
+                self.push(entry.user_data(POLL_TIMEOUT));
+                self.ring.submit_and_wait(1)?
+            }
+            None => self.ring.submit_and_wait(1)?,
+        };
+        Ok(())
+    }
+
+    /// Handles a completion, returning the key to report, if any.
+    fn complete(&mut self, user_data: u64, result: i32) -> Option<reactor::Key> {
+        match self.pending.remove(&user_data)? {
+            Pending::Poll {
+                fd,
+                flags,
+                key,
+                rearm,
+            } => {
+                if result < 0 {
+                    return None;
+                }
+                if rearm {
+                    self.start_poll(fd, flags, key, rearm);
+                }
+                Some(key)
+            }
+            Pending::Signal { fd, key } => {
+                let size = std::mem::size_of::<libc::signalfd_siginfo>();
+                let mut info = unsafe { std::mem::zeroed::<libc::signalfd_siginfo>() };
+                while unsafe { libc::read(fd, &mut info as *mut _ as *mut _, size) } > 0 {}
+                self.start_signal(fd, key);
+                Some(key)
+            }
+            Pending::Timer {
+                timespec,
+                key,
+                repeat,
+            } => {
+                if result != -libc::ETIME {
+                    return None;
+                }
+                if repeat {
+                    self.start_timer(timespec, key, repeat);
+                }
+                Some(key)
+            }
+            Pending::Removed { .. } => None,
+            Pending::Op { key, buf } => {
+                let key = key?;
+                self.ops.remove(&key.inner());
+                self.completions
+                    .insert(key.inner(), Completion { result, buf });
+                Some(key)
+            }
+        }
+    }
+}
+
+impl Backend for Uring {
+    fn add_fd_read(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
+        self.start_poll(fd, libc::POLLIN as u32, key, true);
+        Ok(())
+    }
+
+    fn add_fd_write(&mut self, fd: RawFd, key: reactor::Key) -> std::io::Result<()> {
+        let pending = self.pending.values().any(|pending| {
+            matches!(pending, Pending::Poll { key: polled, rearm: false, .. } if *polled == key)
+        });
+        if !pending {
+            self.start_poll(fd, libc::POLLOUT as u32, key, false);
+        }
+        Ok(())
+    }
+
+    fn remove_fd(&mut self, fd: RawFd) -> std::io::Result<()> {
+        let polls: Vec<u64> = self
+            .pending
+            .iter()
+            .filter_map(|(user_data, pending)| match pending {
+                Pending::Poll { fd: polled, .. } if *polled == fd => Some(*user_data),
+                _ => None,
+            })
+            .collect();
+        for user_data in polls {
+            self.pending.remove(&user_data);
+            self.push(
+                opcode::PollRemove::new(user_data)
+                    .build()
+                    .user_data(IGNORED),
+            );
+        }
+        Ok(())
+    }
+
+    fn add_timer(
+        &mut self,
+        duration: chrono::Duration,
+        key: reactor::Key,
+        repeat: bool,
+    ) -> std::io::Result<()> {
+        self.start_timer(Box::new(timespec(duration)), key, repeat);
+        Ok(())
+    }
+
+    fn remove_timer(&mut self, key: reactor::Key) -> std::io::Result<()> {
+        let timer = self
+            .pending
+            .iter()
+            .find_map(|(user_data, pending)| match pending {
+                Pending::Timer { key: timer, .. } if *timer == key => Some(*user_data),
+                _ => None,
+            });
+        if let Some(user_data) = timer {
+            if let Some(Pending::Timer { timespec, .. }) = self.pending.remove(&user_data) {
+                self.pending.insert(
+                    user_data,
+                    Pending::Removed {
+                        _timespec: timespec,
+                    },
+                );
+            }
+            self.push(
+                opcode::TimeoutRemove::new(user_data)
+                    .build()
+                    .user_data(IGNORED),
+            );
+        }
+        Ok(())
+    }
+
+    fn add_signal(&mut self, signal: i32, key: reactor::Key) -> std::io::Result<()> {
+        let fd = unsafe {
+            let mut mask = std::mem::zeroed::<libc::sigset_t>();
+            libc::sigemptyset(&mut mask);
+            libc::sigaddset(&mut mask, signal);
+            libc::pthread_sigmask(libc::SIG_BLOCK, &mask, std::ptr::null_mut());
+            libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC)
+        };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        self.start_signal(fd, key);
+        Ok(())
+    }
+
+    fn poll(
+        &mut self,
+        events: &mut Events,
+        timeout: Option<chrono::Duration>,
+    ) -> std::io::Result<()> {
+        self.submit_and_wait(timeout)?;
+
+        let completed: Vec<(u64, i32)> = self
+            .ring
+            .completion()
+            .map(|cqe| (cqe.user_data(), cqe.result()))
+            .collect();
+        let mut timed_out = match timeout {
+            Some(timeout) => timeout <= chrono::Duration::zero(),
+            None => true,
+        };
+        for (user_data, result) in completed {
+            debug!("polling: user_data: {} result: {}", user_data, result);
+            match user_data {
+                IGNORED => {}
+                POLL_TIMEOUT => timed_out = true,
+                user_data => {
+                    if let Some(key) = self.complete(user_data, result) {
+                        events.push(key);
+                    }
+                }
+            }
+        }
+        if !timed_out {
+            let entry = opcode::TimeoutRemove::new(POLL_TIMEOUT).build();
+            self.push(entry.user_data(IGNORED));
+        }
+        Ok(())
+    }
+
+    fn supports_ops(&self) -> bool {
+        true
+    }
+
+    fn submit(&mut self, op: Op, key: reactor::Key) -> Result<(), Op> {
+        let (entry, buf) = match op {
+            Op::Read { fd, mut buf } => {
+                let entry =
+                    opcode::Read::new(types::Fd(fd), buf.as_mut_ptr(), buf.len() as u32).build();
+                (entry, Some(buf))
+            }
+            Op::Write { fd, buf } => {
+                let entry =
+                    opcode::Write::new(types::Fd(fd), buf.as_ptr(), buf.len() as u32).build();
+                (entry, Some(buf))
+            }
+            Op::Accept { fd } => {
+                let entry =
+                    opcode::Accept::new(types::Fd(fd), std::ptr::null_mut(), std::ptr::null_mut())
+                        .flags(libc::SOCK_CLOEXEC)
+                        .build();
+                (entry, None)
+            }
+        };
+        let user_data = self.start(
+            entry,
+            Pending::Op {
+                key: Some(key),
+                buf,
+            },
+        );
+        self.ops.insert(key.inner(), user_data);
+        Ok(())
+    }
+
+    fn take_completion(&mut self, key: reactor::Key) -> Option<Completion> {
+        self.completions.remove(&key.inner())
+    }
+
+    fn cancel(&mut self, key: reactor::Key) {
+        self.completions.remove(&key.inner());
+        if let Some(user_data) = self.ops.remove(&key.inner()) {
+            if let Some(Pending::Op { key, .. }) = self.pending.get_mut(&user_data) {
+                key.take();
+            }
+            self.push(
+                opcode::AsyncCancel::new(user_data)
+                    .build()
+                    .user_data(IGNORED),
+            );
+        }
+    }
+}
+
+impl Drop for Uring {
+    fn drop(&mut self) {
+        let ops: Vec<u64> = self
+            .pending
+            .iter()
+            .filter_map(|(user_data, pending)| match pending {
+                Pending::Op { .. } => Some(*user_data),
+                _ => None,
+            })
+            .collect();
+        for user_data in &ops {
+            self.push(
+                opcode::AsyncCancel::new(*user_data)
+                    .build()
+                    .user_data(IGNORED),
+            );
+        }
+
+        // The kernel may still write into the buffers of cancelled operations,
+        // so wait for them before releasing the buffers.
+        let mut remaining = ops.len();
+        while remaining > 0 {
+            if self.ring.submit_and_wait(1).is_err() {
+                std::mem::forget(std::mem::take(&mut self.pending));
+                break;
+            }
+            let completed: Vec<u64> = self.ring.completion().map(|cqe| cqe.user_data()).collect();
+            for user_data in completed {
+                if let Some(Pending::Op { .. }) = self.pending.get(&user_data) {
+                    self.pending.remove(&user_data);
+                    remaining -= 1;
+                }
+            }
+        }
+
+        for pending in self.pending.values() {
+            if let Pending::Signal { fd, .. } = pending {
+                unsafe { libc::close(*fd) };
+            }
+        }
+    }
+}
@@ -1,114 +1,212 @@
-mod node;
+mod task;
 
-use std::cell::{Cell, RefCell};
+use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::pin::Pin;
-use std::rc::{Rc, Weak};
-use std::task::{RawWaker, RawWakerVTable, Waker};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::ThreadId;
 
-use node::{Node, Task};
+use slab::Slab;
+
+use crate::executor::reactor::Notifier;
+use task::Header;
+pub(crate) use task::{State, Task};
 
-#[derive(Default)]
 pub struct Scheduler {
-    inner: Rc<RefCell<Inner>>,
+    shared: Arc<Shared>,
+    tasks: RefCell<Slab<Entry>>,
 }
 
-pub struct Handle {
-    inner: Weak<RefCell<Inner>>,
+struct Entry {
+    header: Arc<Header>,
+    // Taken while the task is polled.
+    future: Option<Pin<Task>>,
 }
 
-#[derive(Default)]
-struct Inner {
-    nodes: VecDeque<Node>,
+/// The part of the scheduler which wakers reach, from any thread.
+struct Shared {
+    queue: Mutex<VecDeque<Arc<Header>>>,
+    // Whether the root future of `block_on` has been woken.
+    woken: AtomicBool,
+    // Whether the executor is blocked on the reactor.
+    parked: AtomicBool,
+    owner: ThreadId,
+    notifier: Notifier,
+}
+
+impl Shared {
+    fn queue(&self) -> MutexGuard<'_, VecDeque<Arc<Header>>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, header: Arc<Header>) {
+        self.queue().push_back(header);
+        self.notify();
+    }
+
+    fn notify(&self) {
+        // The executor thread checks the queue before it parks again.
+        if self.parked.load(Ordering::SeqCst) && std::thread::current().id() != self.owner {
+            if let Err(e) = self.notifier.notify() {
+                log::error!("notify: {}", e);
+            }
+        }
+    }
+}
+
+impl Wake for Shared {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.notify();
+    }
 }
 
 impl Scheduler {
-    pub fn handle(&self) -> Handle {
-        Handle {
-            inner: Rc::downgrade(&self.inner),
+    /// Creates a scheduler running on the current thread. `notifier` is
+    /// triggered when a task is woken from another thread while parked.
+    pub fn new(notifier: Notifier) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                queue: Mutex::new(VecDeque::new()),
+                woken: AtomicBool::new(false),
+                parked: AtomicBool::new(false),
+                owner: std::thread::current().id(),
+                notifier,
+            }),
+            tasks: RefCell::new(Slab::new()),
         }
     }
 
+    /// The waker of the root future, which does not belong to a task.
     pub fn waker(&self) -> Waker {
-        let ptr = Rc::into_raw(self.inner.clone()) as *const ();
-        let vtable =
-            &RawWakerVTable::new(waker::clone, waker::wake, waker::wake_by_ref, waker::drop);
-        unsafe { Waker::from_raw(RawWaker::new(ptr, vtable)) }
+        Waker::from(self.shared.clone())
     }
 
-    pub fn schedule(&mut self, item: Pin<Task>) {
-        log::info!("schedule");
-        let node = Node {
-            scheduler: self.handle(),
-            item: Cell::new(Some(item)),
-        };
+    pub fn schedule(&self, future: Pin<Task>) {
+        let mut tasks = self.tasks.borrow_mut();
+        let entry = tasks.vacant_entry();
+        let header = Header::new(entry.key(), Arc::downgrade(&self.shared));
+        entry.insert(Entry {
+            header: header.clone(),
+            future: Some(future),
+        });
+        self.shared.queue().push_back(header);
+        log::info!("schedule: {} tasks", tasks.len());
+    }
 
-        self.inner.borrow_mut().nodes.push_back(node);
-        log::info!("schedule: {}", self.inner.borrow().nodes.len());
-    }
-
-    pub fn tick(&self) {
-        use std::task::{Context, Poll};
-        log::debug!("tick: {} nodes", self.inner.borrow().nodes.len());
-        let popped = self.inner.borrow_mut().nodes.pop_front();
-        if let Some(node) = popped {
-            let mut task = node.item.take().unwrap();
-            let node = Rc::new(node);
-            let ptr = &node as &Node as *const Node as *const ();
-            let vtable = &RawWakerVTable::new(
-                node::waker::clone,
-                node::waker::wake,
-                node::waker::wake_by_ref,
-                node::waker::drop,
-            );
-            let waker = unsafe { Waker::from_raw(RawWaker::new(ptr, vtable)) };
-            let mut cx = Context::from_waker(&waker);
-            match task.as_mut().poll(&mut cx) {
-                Poll::Ready(_) => {
-                    log::warn!("Ready");
-                }
-                Poll::Pending => {
-                    log::warn!("Pending: {}", Rc::strong_count(&node));
-                    node.item.replace(Some(task));
-                }
-            }
+    pub fn is_empty(&self) -> bool {
+        self.shared.queue().is_empty()
+    }
+
+    /// The number of tasks which have not completed, whether they are woken
+    /// or not.
+    pub fn task_count(&self) -> usize {
+        self.tasks.borrow().len()
+    }
+
+    /// Drops the tasks which are ready to run.
+    pub fn clear(&self) {
+        let queued = std::mem::take(&mut *self.shared.queue());
+        for header in queued {
+            let removed = self.remove(&header);
+            drop(removed);
         }
     }
-}
 
-mod waker {
-    use super::Inner;
-    use std::rc::Rc;
-    use std::task::{RawWaker, RawWakerVTable};
+    /// Drops every task, whether it is ready to run or not.
+    pub fn cancel(&self) {
+        self.shared.queue().clear();
+        // Dropped tasks may spawn or wake others, which are dropped as well.
+        loop {
+            let tasks = std::mem::take(&mut *self.tasks.borrow_mut());
+            if tasks.is_empty() {
+                break;
+            }
+            drop(tasks);
+        }
+        self.shared.queue().clear();
+    }
 
-    pub(super) unsafe fn clone(data: *const ()) -> RawWaker {
-        let rc: Rc<Inner> = Rc::from_raw(data as *const super::Inner);
-        let cloned = rc.clone();
-        log::debug!(
-            "waker::clone (S{}, W{})",
-            Rc::strong_count(&rc),
-            Rc::weak_count(&rc)
-        );
-        std::mem::forget(rc);
-        std::mem::forget(cloned);
+    /// Returns whether the executor may block on the reactor, as no task or
+    /// root future has been woken. Wakers notify the reactor from now on,
+    /// until `unpark`.
+    pub fn park(&self) -> bool {
+        self.shared.parked.store(true, Ordering::SeqCst);
+        if !self.is_empty() || self.shared.woken.swap(false, Ordering::SeqCst) {
+            self.unpark();
+            return false;
+        }
+        true
+    }
 
-        let vtable = &RawWakerVTable::new(clone, wake, wake_by_ref, drop);
-        RawWaker::new(data, vtable)
+    pub fn unpark(&self) {
+        self.shared.parked.store(false, Ordering::SeqCst);
     }
 
-    pub(super) unsafe fn wake(data: *const ()) {
-        log::debug!("waker::wake");
-        let _rc: Rc<Inner> = Rc::from_raw(data as *const Inner);
+    /// Polls the tasks which have been woken, up to `budget` of them so that
+    /// the reactor gets its turn. Tasks woken meanwhile are queued behind.
+    /// Returns the number of tasks polled.
+    pub fn run(&self, budget: usize) -> usize {
+        let mut polled = 0;
+        while polled < budget && self.tick() {
+            polled += 1;
+        }
+        polled
     }
 
-    pub(super) unsafe fn wake_by_ref(data: *const ()) {
-        log::debug!("waker::wake_by_ref");
-        let rc: Rc<Inner> = Rc::from_raw(data as *const Inner);
-        std::mem::forget(rc);
+    /// Polls the next task which has been woken. Returns whether there was
+    /// one.
+    pub fn tick(&self) -> bool {
+        let popped = self.shared.queue().pop_front();
+        let header = match popped {
+            Some(header) => header,
+            None => return false,
+        };
+        let future = match self.tasks.borrow_mut().get_mut(header.key) {
+            // A waker of a completed task may outlive it.
+            Some(entry) if Arc::ptr_eq(&entry.header, &header) => entry.future.take(),
+            _ => None,
+        };
+        let mut future = match future {
+            Some(future) => future,
+            None => return true,
+        };
+
+        header.state.start();
+        let waker = Header::waker(&header);
+        let mut cx = Context::from_waker(&waker);
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(()) => {
+                self.remove(&header);
+            }
+            Poll::Pending => {
+                if let Some(entry) = self.tasks.borrow_mut().get_mut(header.key) {
+                    entry.future = Some(future);
+                }
+                if header.state.stop() {
+                    self.shared.queue().push_back(header);
+                }
+            }
+        }
+        true
     }
 
-    pub(super) unsafe fn drop(data: *const ()) {
-        log::debug!("waker::drop");
-        std::mem::drop(Rc::<Inner>::from_raw(data as *const Inner));
+    /// Removes a task. The root future is woken once the last one is gone.
+    fn remove(&self, header: &Arc<Header>) -> Option<Pin<Task>> {
+        let mut tasks = self.tasks.borrow_mut();
+        let removed = match tasks.get(header.key) {
+            Some(entry) if Arc::ptr_eq(&entry.header, header) => tasks.remove(header.key).future,
+            _ => None,
+        };
+        if tasks.is_empty() {
+            self.shared.woken.store(true, Ordering::SeqCst);
+        }
+        removed
     }
 }
@@ -0,0 +1,114 @@
+use std::future::Future;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{RawWaker, RawWakerVTable, Waker};
+
+use crate::executor::scheduler::Shared;
+
+pub(crate) type Task = Box<dyn Future<Output = ()>>;
+
+/// In the run queue.
+const QUEUED: u8 = 1;
+/// Being polled.
+const RUNNING: u8 = 1 << 1;
+/// Woken while being polled, so it has to be queued again afterwards.
+const NOTIFIED: u8 = 1 << 2;
+
+/// Keeps a task in a run queue at most once, however often it is woken.
+pub(crate) struct State(AtomicU8);
+
+impl State {
+    /// The state of a task which is about to be queued.
+    pub(crate) fn new() -> Self {
+        Self(AtomicU8::new(QUEUED))
+    }
+
+    /// Marks the task as popped from the queue, and being polled.
+    pub(crate) fn start(&self) {
+        self.0.store(RUNNING, Ordering::SeqCst);
+    }
+
+    /// Marks the task as polled. Returns whether it has been woken meanwhile,
+    /// and is now queued again.
+    pub(crate) fn stop(&self) -> bool {
+        let prev = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| {
+                Some(if state & NOTIFIED != 0 { QUEUED } else { 0 })
+            });
+        matches!(prev, Ok(prev) if prev & NOTIFIED != 0)
+    }
+
+    /// Marks the task as woken. Returns whether the caller has to queue it,
+    /// which is not the case if it is queued already, or running and to be
+    /// queued once it is polled.
+    pub(crate) fn wake(&self) -> bool {
+        let prev = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| {
+                if state & (QUEUED | NOTIFIED) != 0 {
+                    None
+                } else if state & RUNNING != 0 {
+                    Some(state | NOTIFIED)
+                } else {
+                    Some(QUEUED)
+                }
+            });
+        matches!(prev, Ok(prev) if prev & RUNNING == 0)
+    }
+}
+
+/// The reference-counted part of a task, which its wakers point to. The future
+/// itself stays in the scheduler, on the thread running it, so wakers can be
+/// sent anywhere.
+pub(super) struct Header {
+    pub(super) key: usize,
+    pub(super) state: State,
+    scheduler: Weak<Shared>,
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+
+impl Header {
+    /// Creates the header of a task which is about to be queued.
+    pub(super) fn new(key: usize, scheduler: Weak<Shared>) -> Arc<Self> {
+        Arc::new(Self {
+            key,
+            state: State::new(),
+            scheduler,
+        })
+    }
+
+    pub(super) fn waker(this: &Arc<Self>) -> Waker {
+        let data = Arc::into_raw(this.clone()) as *const ();
+        unsafe { Waker::from_raw(RawWaker::new(data, &VTABLE)) }
+    }
+
+    /// Queues the task, unless it is queued already, or the scheduler is gone.
+    fn schedule(this: Arc<Self>) {
+        if this.state.wake() {
+            if let Some(scheduler) = this.scheduler.upgrade() {
+                scheduler.push(this);
+            }
+        }
+    }
+}
+
+unsafe fn clone(data: *const ()) -> RawWaker {
+    Arc::increment_strong_count(data as *const Header);
+    RawWaker::new(data, &VTABLE)
+}
+
+unsafe fn wake(data: *const ()) {
+    Header::schedule(Arc::from_raw(data as *const Header));
+}
+
+unsafe fn wake_by_ref(data: *const ()) {
+    let header = ManuallyDrop::new(Arc::from_raw(data as *const Header));
+    Header::schedule(Arc::clone(&header));
+}
+
+unsafe fn drop(data: *const ()) {
+    std::mem::drop(Arc::from_raw(data as *const Header));
+}
@@ -9,9 +9,9 @@ pub struct Signal {
 }
 
 impl Signal {
-    pub fn start(reactor: reactor::Handle, signal: i32) -> Result<Self, failure::Error> {
+    pub fn start(reactor: reactor::Handle, signal: i32) -> Result<Self, crate::Error> {
         Ok(Self {
-            key: reactor.add_signal(signal),
+            key: reactor.add_signal(signal)?,
             reactor,
         })
     }
@@ -23,7 +23,10 @@ impl futures::Stream for Signal {
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         match futures::ready!(self.reactor.poll_elapsed(cx, self.key)) {
             Ok(_) => Poll::Ready(Some(())),
-            Err(e) => panic!("timer error: {}", e),
+            Err(e) => {
+                log::error!("signal error: {}", e);
+                Poll::Ready(None)
+            }
         }
     }
 }
@@ -4,22 +4,16 @@ use std::task::{Context, Poll};
 
 use crate::executor::reactor;
 
-#[derive(Debug, Fail)]
-pub enum Error {
-    #[fail(display = "fcntl(F_GETFL) returned -1")]
-    GetFl,
-}
-
 pub struct Stdin {
     inner: std::io::Stdin,
     register: reactor::Register,
 }
 
-pub fn stdin(reactor: reactor::Handle) -> Result<BufReader<Stdin>, failure::Error> {
+pub fn stdin(reactor: reactor::Handle) -> Result<BufReader<Stdin>, crate::Error> {
     unsafe {
         let prev = libc::fcntl(libc::STDIN_FILENO, libc::F_GETFL);
         if prev < 0 {
-            return Err(Error::GetFl.into());
+            return Err(std::io::Error::last_os_error().into());
         }
         libc::fcntl(libc::STDIN_FILENO, libc::F_SETFL, prev | libc::O_NONBLOCK);
     }
@@ -42,7 +36,7 @@ impl AsyncRead for Stdin {
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                 log::warn!("WouldBlock");
                 let fd = libc::STDIN_FILENO;
-                self.register.register_read(cx, fd).unwrap();
+                self.register.register_read(cx, fd)?;
                 Poll::Pending
             }
             etc => Poll::Ready(etc),
@@ -9,7 +9,7 @@ pub struct Stdout {
     register: reactor::Register,
 }
 
-pub fn stdout(reactor: reactor::Handle) -> Result<Stdout, failure::Error> {
+pub fn stdout(reactor: reactor::Handle) -> Result<Stdout, crate::Error> {
     Ok(Stdout {
         inner: std::io::stdout(),
         register: reactor::Register::new(reactor),
@@ -26,7 +26,7 @@ impl AsyncWrite for Stdout {
         match self.inner.write(buf) {
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                 let fd = libc::STDOUT_FILENO;
-                self.register.register_write(cx, fd).unwrap();
+                self.register.register_write(cx, fd)?;
                 Poll::Pending
             }
             etc => Poll::Ready(etc),
@@ -38,7 +38,7 @@ impl AsyncWrite for Stdout {
         match self.inner.flush() {
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                 let fd = libc::STDOUT_FILENO;
-                self.register.register_write(cx, fd).unwrap();
+                self.register.register_write(cx, fd)?;
                 Poll::Pending
             }
             etc => Poll::Ready(etc),
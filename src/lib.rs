@@ -1,13 +1,14 @@
-#![feature(try_trait)]
-
 #[macro_use]
 extern crate log;
-#[macro_use]
-extern crate failure;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 #[macro_use]
 extern crate num_derive;
 
+mod error;
 pub mod executor;
 pub mod io;
 pub mod net;
+pub mod runtime;
 pub mod timer;
+
+pub use error::Error;
@@ -1,5 +1,10 @@
+mod socket;
+mod split;
 mod tcp_listener;
+mod tcp_socket;
 mod tcp_stream;
 
+pub use split::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, ReuniteError, WriteHalf};
 pub use tcp_listener::TcpListener;
-pub use tcp_stream::TcpStream;
+pub use tcp_socket::{Keepalive, TcpSocket};
+pub use tcp_stream::{ReadOwned, TcpStream, WriteOwned};
@@ -0,0 +1,106 @@
+use std::net::SocketAddr;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// The backlog of listeners bound by dope itself.
+pub(super) const BACKLOG: u32 = 1024;
+
+fn check(res: libc::c_int) -> std::io::Result<libc::c_int> {
+    if res == -1 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(res)
+    }
+}
+
+pub(super) fn set_option<T>(
+    fd: RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: T,
+) -> std::io::Result<()> {
+    check(unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const T as *const _,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    })?;
+    Ok(())
+}
+
+fn sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr = libc::in_addr {
+                s_addr: u32::from_ne_bytes(addr.ip().octets()),
+            };
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            {
+                sin.sin_len = std::mem::size_of::<libc::sockaddr_in>() as u8;
+            }
+            std::mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr = libc::in6_addr {
+                s6_addr: addr.ip().octets(),
+            };
+            sin6.sin6_scope_id = addr.scope_id();
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            {
+                sin6.sin6_len = std::mem::size_of::<libc::sockaddr_in6>() as u8;
+            }
+            std::mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+/// Creates a non-blocking socket for `addr`.
+pub(super) fn socket(addr: &SocketAddr) -> std::io::Result<OwnedFd> {
+    let domain = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    let socket =
+        unsafe { OwnedFd::from_raw_fd(check(libc::socket(domain, libc::SOCK_STREAM, 0))?) };
+    let fd = socket.as_raw_fd();
+    check(unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    let flags = check(unsafe { libc::fcntl(fd, libc::F_GETFL) })?;
+    check(unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+    Ok(socket)
+}
+
+pub(super) fn bind(fd: RawFd, addr: &SocketAddr) -> std::io::Result<()> {
+    let (storage, len) = sockaddr(addr);
+    check(unsafe { libc::bind(fd, &storage as *const _ as *const libc::sockaddr, len) })?;
+    Ok(())
+}
+
+pub(super) fn listen(fd: RawFd, backlog: u32) -> std::io::Result<()> {
+    let backlog = std::cmp::min(backlog, libc::c_int::MAX as u32) as libc::c_int;
+    check(unsafe { libc::listen(fd, backlog) })?;
+    Ok(())
+}
+
+/// Starts connecting a non-blocking socket to `addr`. Returns whether the
+/// connection is still in progress.
+pub(super) fn connect(fd: RawFd, addr: &SocketAddr) -> std::io::Result<bool> {
+    let (storage, len) = sockaddr(addr);
+    let res =
+        check(unsafe { libc::connect(fd, &storage as *const _ as *const libc::sockaddr, len) });
+    match res {
+        Ok(_) => Ok(false),
+        Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => Ok(true),
+        Err(e) => Err(e),
+    }
+}
@@ -1,16 +1,20 @@
+use std::future::Future;
 use std::net::{SocketAddr, ToSocketAddrs};
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use futures::{ready, Stream};
 
-use super::TcpStream;
+use super::{socket, TcpSocket, TcpStream};
 use crate::executor::reactor;
 
 pub struct TcpListener {
-    inner: std::net::TcpListener,
     register: reactor::Register,
+    // An accept submitted to a completion-based backend.
+    accept: Option<reactor::Operation>,
+    // Dropped last, once the registration and the accept are released.
+    inner: std::net::TcpListener,
 }
 
 pub struct Incoming {
@@ -18,7 +22,7 @@ pub struct Incoming {
 }
 
 impl Stream for Incoming {
-    type Item = Result<TcpStream, failure::Error>;
+    type Item = Result<TcpStream, crate::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let (socket, _) = ready!(self.inner.poll_accept(cx))?;
@@ -27,18 +31,56 @@ impl Stream for Incoming {
 }
 
 impl TcpListener {
-    pub fn bind<A: ToSocketAddrs>(
+    pub fn bind<A: ToSocketAddrs>(reactor: reactor::Handle, addr: A) -> Result<Self, crate::Error> {
+        Self::from_std(reactor, std::net::TcpListener::bind(addr)?)
+    }
+
+    /// Like `bind`, but with `SO_REUSEPORT`, so that every thread of a
+    /// `runtime::PerCore` may bind the same address and accept its own share
+    /// of the connections.
+    pub fn bind_reuse_port<A: ToSocketAddrs>(
         reactor: reactor::Handle,
         addr: A,
-    ) -> Result<Self, failure::Error> {
-        let inner = std::net::TcpListener::bind(addr)?;
+    ) -> Result<Self, crate::Error> {
+        let mut last = None;
+        for addr in addr.to_socket_addrs()? {
+            match Self::listen_reuse_port(reactor.clone(), &addr) {
+                Ok(listener) => return Ok(listener),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address to bind").into()
+        }))
+    }
+
+    fn listen_reuse_port(
+        reactor: reactor::Handle,
+        addr: &SocketAddr,
+    ) -> Result<Self, crate::Error> {
+        let socket = TcpSocket::new_for_addr(addr)?;
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(true)?;
+        socket.bind(addr)?;
+        socket.listen(reactor, socket::BACKLOG)
+    }
+
+    pub(super) fn from_std(
+        reactor: reactor::Handle,
+        inner: std::net::TcpListener,
+    ) -> Result<Self, crate::Error> {
         inner.set_nonblocking(true)?;
         Ok(Self {
             inner,
             register: reactor::Register::new(reactor),
+            accept: None,
         })
     }
 
+    pub fn local_addr(&self) -> Result<SocketAddr, crate::Error> {
+        Ok(self.inner.local_addr()?)
+    }
+
     pub fn incoming(self) -> Incoming {
         Incoming { inner: self }
     }
@@ -46,7 +88,29 @@ impl TcpListener {
     fn poll_accept(
         &mut self,
         cx: &mut Context<'_>,
-    ) -> Poll<Result<(TcpStream, SocketAddr), failure::Error>> {
+    ) -> Poll<Result<(TcpStream, SocketAddr), crate::Error>> {
+        if self.accept.is_none() && self.register.supports_ops() {
+            let fd = self.inner.as_raw_fd();
+            if let Ok(operation) = self
+                .register
+                .clone_reactor()
+                .submit(cx, reactor::Op::Accept { fd })
+            {
+                self.accept = Some(operation);
+            }
+        }
+        if let Some(operation) = self.accept.as_mut() {
+            let completion = ready!(Pin::new(operation).poll(cx))?;
+            self.accept = None;
+            let std_stream =
+                unsafe { std::net::TcpStream::from_raw_fd(completion.result()? as i32) };
+            let addr = std_stream.peer_addr()?;
+            log::info!("accepted: {:?}", addr);
+
+            let res = TcpStream::new(self.register.clone_reactor(), std_stream)?;
+            return Poll::Ready(Ok((res, addr)));
+        }
+
         let (std_stream, addr) = match self.inner.accept() {
             Ok(pair) => pair,
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -54,7 +118,7 @@ impl TcpListener {
                 self.register.register_read(cx, fd)?;
                 return Poll::Pending;
             }
-            Err(e) => return Poll::Ready(Err(failure::Error::from(e))),
+            Err(e) => return Poll::Ready(Err(crate::Error::from(e))),
         };
         log::info!("accepted: {:?}", addr);
 
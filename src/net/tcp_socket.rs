@@ -0,0 +1,235 @@
+use std::mem::ManuallyDrop;
+use std::net::{self, SocketAddr};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::task::{Context, Poll};
+
+use super::{socket, TcpListener, TcpStream};
+use crate::executor::reactor;
+
+#[cfg(target_os = "linux")]
+const KEEPALIVE_TIME: libc::c_int = libc::TCP_KEEPIDLE;
+#[cfg(not(target_os = "linux"))]
+const KEEPALIVE_TIME: libc::c_int = libc::TCP_KEEPALIVE;
+
+// `SO_LINGER` counts in ticks on Apple platforms.
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+const LINGER: libc::c_int = libc::SO_LINGER;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const LINGER: libc::c_int = libc::SO_LINGER_SEC;
+
+/// A TCP socket which is configured before it listens or connects, as with
+/// `socket2`.
+///
+/// ```no_run
+/// # async fn f(reactor: dope::executor::reactor::Handle) -> Result<(), dope::Error> {
+/// use dope::net::TcpSocket;
+///
+/// let addr = "127.0.0.1:8080".parse().unwrap();
+/// let socket = TcpSocket::new_for_addr(&addr)?;
+/// socket.set_reuseaddr(true)?;
+/// socket.bind(&addr)?;
+/// let listener = socket.listen(reactor, 128)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TcpSocket {
+    inner: OwnedFd,
+}
+
+/// Keepalive parameters, for `TcpSocket::set_keepalive`. The ones left unset
+/// keep the defaults of the system.
+#[derive(Clone, Debug, Default)]
+pub struct Keepalive {
+    time: Option<chrono::Duration>,
+    interval: Option<chrono::Duration>,
+    retries: Option<u32>,
+}
+
+impl Keepalive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long the connection stays idle before the first probe is sent.
+    pub fn time(mut self, time: chrono::Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// How long to wait between probes without an answer.
+    pub fn interval(mut self, interval: chrono::Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// The number of probes without an answer before the connection drops.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+}
+
+fn secs(duration: chrono::Duration) -> libc::c_int {
+    std::cmp::min(
+        std::cmp::max(duration.num_seconds(), 0),
+        i64::from(libc::c_int::MAX),
+    ) as libc::c_int
+}
+
+impl TcpSocket {
+    pub fn new_v4() -> Result<Self, crate::Error> {
+        Self::new_for_addr(&SocketAddr::from(([0, 0, 0, 0], 0)))
+    }
+
+    pub fn new_v6() -> Result<Self, crate::Error> {
+        Self::new_for_addr(&SocketAddr::from(([0; 16], 0)))
+    }
+
+    /// A socket of the family of `addr`.
+    pub fn new_for_addr(addr: &SocketAddr) -> Result<Self, crate::Error> {
+        Ok(Self {
+            inner: socket::socket(addr)?,
+        })
+    }
+
+    fn set_option<T>(
+        &self,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: T,
+    ) -> Result<(), crate::Error> {
+        Ok(socket::set_option(
+            self.inner.as_raw_fd(),
+            level,
+            name,
+            value,
+        )?)
+    }
+
+    pub fn set_reuseaddr(&self, reuseaddr: bool) -> Result<(), crate::Error> {
+        self.set_option(
+            libc::SOL_SOCKET,
+            libc::SO_REUSEADDR,
+            libc::c_int::from(reuseaddr),
+        )
+    }
+
+    /// Lets other sockets with the option bind the same address. Linux spreads
+    /// the incoming connections among them, while macOS hands them all to the
+    /// last one.
+    pub fn set_reuseport(&self, reuseport: bool) -> Result<(), crate::Error> {
+        self.set_option(
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            libc::c_int::from(reuseport),
+        )
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> Result<(), crate::Error> {
+        self.set_option(
+            libc::IPPROTO_TCP,
+            libc::TCP_NODELAY,
+            libc::c_int::from(nodelay),
+        )
+    }
+
+    /// Enables keepalive probes with `keepalive`, or disables them if `None`.
+    pub fn set_keepalive(&self, keepalive: Option<Keepalive>) -> Result<(), crate::Error> {
+        let keepalive = match keepalive {
+            Some(keepalive) => keepalive,
+            None => return self.set_option(libc::SOL_SOCKET, libc::SO_KEEPALIVE, 0),
+        };
+        self.set_option(libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+        if let Some(time) = keepalive.time {
+            self.set_option(libc::IPPROTO_TCP, KEEPALIVE_TIME, secs(time))?;
+        }
+        if let Some(interval) = keepalive.interval {
+            self.set_option(libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, secs(interval))?;
+        }
+        if let Some(retries) = keepalive.retries {
+            let retries = std::cmp::min(retries, libc::c_int::MAX as u32) as libc::c_int;
+            self.set_option(libc::IPPROTO_TCP, libc::TCP_KEEPCNT, retries)?;
+        }
+        Ok(())
+    }
+
+    /// Makes closing the socket wait up to `linger` for the unsent data, in
+    /// seconds. `None` closes it right away, sending the data in the
+    /// background.
+    pub fn set_linger(&self, linger: Option<chrono::Duration>) -> Result<(), crate::Error> {
+        let linger = libc::linger {
+            l_onoff: libc::c_int::from(linger.is_some()),
+            l_linger: linger.map_or(0, secs),
+        };
+        self.set_option(libc::SOL_SOCKET, LINGER, linger)
+    }
+
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<(), crate::Error> {
+        let size = std::cmp::min(size, libc::c_int::MAX as usize) as libc::c_int;
+        self.set_option(libc::SOL_SOCKET, libc::SO_SNDBUF, size)
+    }
+
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<(), crate::Error> {
+        let size = std::cmp::min(size, libc::c_int::MAX as usize) as libc::c_int;
+        self.set_option(libc::SOL_SOCKET, libc::SO_RCVBUF, size)
+    }
+
+    /// Binds the socket to `addr`, before it listens, or to pick the local
+    /// address it connects from.
+    pub fn bind(&self, addr: &SocketAddr) -> Result<(), crate::Error> {
+        Ok(socket::bind(self.inner.as_raw_fd(), addr)?)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, crate::Error> {
+        // Borrows the fd, which stays owned by `inner`.
+        let fd = self.inner.as_raw_fd();
+        let std = ManuallyDrop::new(unsafe { net::TcpStream::from_raw_fd(fd) });
+        Ok(std.local_addr()?)
+    }
+
+    /// Listens on the address the socket is bound to, queuing up to `backlog`
+    /// connections which are yet to be accepted.
+    pub fn listen(
+        self,
+        reactor: reactor::Handle,
+        backlog: u32,
+    ) -> Result<TcpListener, crate::Error> {
+        socket::listen(self.inner.as_raw_fd(), backlog)?;
+        TcpListener::from_std(reactor, net::TcpListener::from(self.inner))
+    }
+
+    /// Connects to `addr`, waiting for the connection without blocking.
+    pub async fn connect(
+        self,
+        reactor: reactor::Handle,
+        addr: SocketAddr,
+    ) -> Result<TcpStream, crate::Error> {
+        let in_progress = socket::connect(self.inner.as_raw_fd(), &addr)?;
+        let inner = net::TcpStream::from(self.inner);
+        if in_progress {
+            // Dropped before the stream, which registers on its own.
+            let mut register = reactor::Register::new(reactor.clone());
+            futures::future::poll_fn(|cx| poll_connected(&inner, &mut register, cx)).await?;
+        }
+        TcpStream::new(reactor, inner)
+    }
+}
+
+/// Waits for a connection in progress, which makes the socket writable.
+fn poll_connected(
+    inner: &net::TcpStream,
+    register: &mut reactor::Register,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), crate::Error>> {
+    if let Some(e) = inner.take_error()? {
+        return Poll::Ready(Err(e.into()));
+    }
+    match inner.peer_addr() {
+        Ok(_) => Poll::Ready(Ok(())),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotConnected => {
+            register.register_write(cx, inner.as_raw_fd())?;
+            Poll::Pending
+        }
+        Err(e) => Poll::Ready(Err(e.into())),
+    }
+}
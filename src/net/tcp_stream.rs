@@ -1,69 +1,314 @@
 use futures::io::{AsyncRead, AsyncWrite};
-use std::net;
+use std::future::Future;
+use std::net::{self, SocketAddr, ToSocketAddrs};
 use std::os::unix::io::AsRawFd;
 use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use std::task::{Context, Poll};
 
+use super::split::{self, OwnedReadHalf, OwnedWriteHalf, ReadHalf, WriteHalf};
+use super::TcpSocket;
 use crate::executor::reactor;
+use crate::timer::Delay;
 
 pub struct TcpStream {
+    // Dropped first, while the fd is still open. Shared by the halves of a
+    // split stream, which wait for read and write readiness apart.
+    register: Mutex<reactor::Register>,
     inner: net::TcpStream,
-    register: reactor::Register,
 }
 
 impl TcpStream {
-    pub fn new(
-        reactor: reactor::Handle,
-        std_stream: net::TcpStream,
-    ) -> Result<Self, failure::Error> {
+    pub fn new(reactor: reactor::Handle, std_stream: net::TcpStream) -> Result<Self, crate::Error> {
         std_stream.set_nonblocking(true)?;
         log::debug!("TcpStream::new(fd: {})", std_stream.as_raw_fd());
         Ok(Self {
-            register: reactor::Register::new(reactor),
+            register: Mutex::new(reactor::Register::new(reactor)),
             inner: std_stream,
         })
     }
-}
 
-impl AsyncRead for TcpStream {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context,
+    fn register(&self) -> MutexGuard<'_, reactor::Register> {
+        self.register.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Connects to `addr`, trying the addresses it resolves to in turn until
+    /// one of them succeeds. Resolving may block, as it does for
+    /// `std::net::TcpStream::connect`.
+    pub async fn connect<A: ToSocketAddrs>(
+        reactor: reactor::Handle,
+        addr: A,
+    ) -> Result<Self, crate::Error> {
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        let mut last = None;
+        for addr in addrs {
+            match Self::connect_addr(reactor.clone(), addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    log::debug!("connect to {}: {}", addr, e);
+                    last = Some(e);
+                }
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address to connect to").into()
+        }))
+    }
+
+    /// Like `connect`, but fails with `TimedOut` if no address could be
+    /// connected to within `timeout`.
+    pub async fn connect_timeout<A: ToSocketAddrs>(
+        reactor: reactor::Handle,
+        addr: A,
+        timeout: chrono::Duration,
+    ) -> Result<Self, crate::Error> {
+        let delay = Delay::start(reactor.clone(), timeout)?;
+        let connect = Box::pin(Self::connect(reactor, addr));
+        match futures::future::select(connect, delay).await {
+            futures::future::Either::Left((res, _)) => res,
+            futures::future::Either::Right((res, _)) => {
+                res?;
+                Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into())
+            }
+        }
+    }
+
+    async fn connect_addr(
+        reactor: reactor::Handle,
+        addr: SocketAddr,
+    ) -> Result<Self, crate::Error> {
+        TcpSocket::new_for_addr(&addr)?.connect(reactor, addr).await
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, crate::Error> {
+        Ok(self.inner.local_addr()?)
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr, crate::Error> {
+        Ok(self.inner.peer_addr()?)
+    }
+
+    pub fn nodelay(&self) -> Result<bool, crate::Error> {
+        Ok(self.inner.nodelay()?)
+    }
+
+    /// Sends small writes right away, rather than coalescing them.
+    pub fn set_nodelay(&self, nodelay: bool) -> Result<(), crate::Error> {
+        Ok(self.inner.set_nodelay(nodelay)?)
+    }
+
+    pub fn ttl(&self) -> Result<u32, crate::Error> {
+        Ok(self.inner.ttl()?)
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> Result<(), crate::Error> {
+        Ok(self.inner.set_ttl(ttl)?)
+    }
+
+    /// Splits the stream into halves which read and write at the same time,
+    /// borrowing it.
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        split::split(self)
+    }
+
+    /// Splits the stream into halves which may move to different tasks. See
+    /// `OwnedReadHalf::reunite`.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        split::into_split(self)
+    }
+
+    pub(super) fn poll_read_ref(
+        &self,
+        cx: &mut Context<'_>,
         buf: &mut [u8],
-    ) -> Poll<Result<usize, std::io::Error>> {
-        log::debug!("poll_read");
+    ) -> Poll<std::io::Result<usize>> {
         use std::io::Read;
 
-        match self.inner.read(buf) {
+        match (&self.inner).read(buf) {
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                let fd = self.inner.as_raw_fd();
+                self.register().register_read(cx, fd)?;
+                Poll::Pending
+            }
+            etc => Poll::Ready(etc),
+        }
+    }
+
+    pub(super) fn poll_write_ref(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        use std::io::Write;
+
+        match (&self.inner).write(buf) {
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                log::warn!("WouldBlock");
                 let fd = self.inner.as_raw_fd();
-                self.register.register_read(cx, fd).unwrap();
+                self.register().register_write(cx, fd)?;
                 Poll::Pending
             }
             etc => Poll::Ready(etc),
         }
     }
+
+    pub(super) fn shutdown(&self, how: net::Shutdown) -> std::io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    /// Reads into `buf`, handing the buffer back with the result. On
+    /// completion-based backends, the read is submitted to the backend instead
+    /// of waiting for readiness.
+    pub fn read_owned(&mut self, buf: Vec<u8>) -> ReadOwned<'_> {
+        ReadOwned {
+            stream: self,
+            state: State::Idle(buf),
+        }
+    }
+
+    /// Writes from `buf`, handing the buffer back with the result. On
+    /// completion-based backends, the write is submitted to the backend instead
+    /// of waiting for readiness.
+    pub fn write_owned(&mut self, buf: Vec<u8>) -> WriteOwned<'_> {
+        WriteOwned {
+            stream: self,
+            state: State::Idle(buf),
+        }
+    }
+}
+
+enum State {
+    Idle(Vec<u8>),
+    Submitted(reactor::Operation),
+    // The backend is readiness-based.
+    Ready(Vec<u8>),
+    Done,
+}
+
+impl State {
+    fn poll_owned<F>(
+        &mut self,
+        stream: &mut TcpStream,
+        cx: &mut Context<'_>,
+        op: fn(std::os::unix::io::RawFd, Vec<u8>) -> reactor::Op,
+        fallback: F,
+    ) -> Poll<(std::io::Result<usize>, Vec<u8>)>
+    where
+        F: FnOnce(
+            Pin<&mut TcpStream>,
+            &mut Context<'_>,
+            &mut Vec<u8>,
+        ) -> Poll<std::io::Result<usize>>,
+    {
+        loop {
+            match std::mem::replace(self, State::Done) {
+                State::Idle(buf) if !stream.register().supports_ops() => {
+                    *self = State::Ready(buf);
+                }
+                State::Idle(buf) => {
+                    let reactor = stream.register().clone_reactor();
+                    match reactor.submit(cx, op(stream.inner.as_raw_fd(), buf)) {
+                        Ok(operation) => *self = State::Submitted(operation),
+                        Err(reactor::Op::Read { buf, .. })
+                        | Err(reactor::Op::Write { buf, .. }) => *self = State::Ready(buf),
+                        Err(reactor::Op::Accept { .. }) => unreachable!(),
+                    }
+                }
+                State::Submitted(mut operation) => {
+                    return match Pin::new(&mut operation).poll(cx) {
+                        Poll::Ready(Ok(completion)) => {
+                            let res = completion.result();
+                            Poll::Ready((res, completion.buf.unwrap_or_default()))
+                        }
+                        // The buffer is lost along with the reactor.
+                        Poll::Ready(Err(e)) => Poll::Ready((Err(e.into()), vec![])),
+                        Poll::Pending => {
+                            *self = State::Submitted(operation);
+                            Poll::Pending
+                        }
+                    };
+                }
+                State::Ready(mut buf) => {
+                    return match fallback(Pin::new(stream), cx, &mut buf) {
+                        Poll::Ready(res) => Poll::Ready((res, buf)),
+                        Poll::Pending => {
+                            *self = State::Ready(buf);
+                            Poll::Pending
+                        }
+                    };
+                }
+                State::Done => panic!("polled after completion"),
+            }
+        }
+    }
+}
+
+pub struct ReadOwned<'a> {
+    stream: &'a mut TcpStream,
+    state: State,
+}
+
+impl Future for ReadOwned<'_> {
+    type Output = (std::io::Result<usize>, Vec<u8>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        this.state.poll_owned(
+            this.stream,
+            cx,
+            |fd, buf| reactor::Op::Read { fd, buf },
+            |stream, cx, buf| stream.poll_read(cx, buf),
+        )
+    }
+}
+
+pub struct WriteOwned<'a> {
+    stream: &'a mut TcpStream,
+    state: State,
+}
+
+impl Future for WriteOwned<'_> {
+    type Output = (std::io::Result<usize>, Vec<u8>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        this.state.poll_owned(
+            this.stream,
+            cx,
+            |fd, buf| reactor::Op::Write { fd, buf },
+            |stream, cx, buf| stream.poll_write(cx, buf),
+        )
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        log::debug!("poll_read");
+        self.poll_read_ref(cx, buf)
+    }
 }
 
 impl AsyncWrite for TcpStream {
     fn poll_write(
-        mut self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
-        use std::io::Write;
-        Poll::Ready(self.inner.write(buf))
+        self.poll_write_ref(cx, buf)
     }
 
     fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        // Writes go to the socket as they are, with nothing buffered here.
         Poll::Ready(Ok(()))
     }
 
     fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
         self.inner.shutdown(std::net::Shutdown::Both)?;
         let fd = self.inner.as_raw_fd();
-        self.register.unregister(fd).unwrap();
+        self.register().unregister(fd)?;
         Poll::Ready(Ok(()))
     }
 }
@@ -0,0 +1,4 @@
+mod affinity;
+mod per_core;
+
+pub use per_core::{Core, Cores, PerCore};
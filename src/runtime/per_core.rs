@@ -0,0 +1,194 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures::channel::oneshot;
+use futures::future::Either;
+
+use crate::executor::{self, Executor};
+use crate::runtime::affinity;
+
+/// Starts an `Executor` per thread, each with a reactor of its own, and
+/// pinned to a core where the platform supports it.
+///
+/// Threads share nothing but the factory, so tasks never move between them.
+/// Listeners bound with `TcpListener::bind_reuse_port` let every thread
+/// accept its own connections.
+pub struct PerCore {
+    threads: usize,
+    pin: bool,
+    name: String,
+}
+
+/// What the factory gets on each thread.
+pub struct Core {
+    index: usize,
+    cpu: Option<usize>,
+    handle: executor::Handle,
+}
+
+/// The threads started by `PerCore`. Dropping it stops and joins them.
+pub struct Cores {
+    threads: Vec<std::thread::JoinHandle<Result<(), crate::Error>>>,
+    stops: Mutex<Vec<oneshot::Sender<()>>>,
+}
+
+impl PerCore {
+    /// A thread per CPU available to the process, pinned.
+    pub fn new() -> Self {
+        Self {
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            pin: true,
+            name: String::from("dope-core"),
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = std::cmp::max(threads, 1);
+        self
+    }
+
+    /// Pins every thread to a CPU, in order, among the ones the process may
+    /// run on. Only supported on Linux, and ignored elsewhere.
+    pub fn pin(mut self, pin: bool) -> Self {
+        self.pin = pin;
+        self
+    }
+
+    /// The prefix of the thread names, followed by their index.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Starts the threads, running the future returned by `factory` on each
+    /// one until it completes, or until `Cores::stop`.
+    pub fn start<F, Fut>(self, factory: F) -> Result<Cores, crate::Error>
+    where
+        F: Fn(Core) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), crate::Error>> + 'static,
+    {
+        let cpus = if self.pin {
+            affinity::allowed()
+        } else {
+            vec![]
+        };
+        let factory = Arc::new(factory);
+        let mut cores = Cores {
+            threads: Vec::with_capacity(self.threads),
+            stops: Mutex::new(Vec::with_capacity(self.threads)),
+        };
+        for index in 0..self.threads {
+            let cpu = match cpus.len() {
+                0 => None,
+                len => Some(cpus[index % len]),
+            };
+            let (stop, stopped) = oneshot::channel();
+            let factory = factory.clone();
+            let thread = std::thread::Builder::new()
+                .name(format!("{}-{}", self.name, index))
+                .spawn(move || run(index, cpu, &*factory, stopped))?;
+            cores.threads.push(thread);
+            cores.stops().push(stop);
+        }
+        Ok(cores)
+    }
+}
+
+impl Default for PerCore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn run<F, Fut>(
+    index: usize,
+    cpu: Option<usize>,
+    factory: &F,
+    stopped: oneshot::Receiver<()>,
+) -> Result<(), crate::Error>
+where
+    F: Fn(Core) -> Fut,
+    Fut: Future<Output = Result<(), crate::Error>>,
+{
+    if let Some(cpu) = cpu {
+        if let Err(e) = affinity::pin(cpu) {
+            log::warn!("pin to cpu {}: {}", cpu, e);
+        }
+    }
+    let executor = Executor::new()?;
+    let future = factory(Core {
+        index,
+        cpu,
+        handle: executor.handle(),
+    });
+    match executor.block_on(futures::future::select(Box::pin(future), stopped))? {
+        Either::Left((res, _)) => res,
+        Either::Right(_) => Ok(()),
+    }
+}
+
+impl Core {
+    /// The index of the thread, from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The CPU which the thread is pinned to, if it is.
+    pub fn cpu(&self) -> Option<usize> {
+        self.cpu
+    }
+
+    /// The executor of the thread.
+    pub fn handle(&self) -> &executor::Handle {
+        &self.handle
+    }
+}
+
+impl Cores {
+    fn stops(&self) -> std::sync::MutexGuard<'_, Vec<oneshot::Sender<()>>> {
+        self.stops.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn len(&self) -> usize {
+        self.threads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.threads.is_empty()
+    }
+
+    /// Makes every thread return from its executor, dropping the futures
+    /// returned by the factory. Does not wait for them.
+    pub fn stop(&self) {
+        self.stops().clear();
+    }
+
+    /// Waits for every thread, and returns the first error of any of them.
+    pub fn join(mut self) -> Result<(), crate::Error> {
+        self.join_all()
+    }
+
+    fn join_all(&mut self) -> Result<(), crate::Error> {
+        let mut res = Ok(());
+        for thread in self.threads.drain(..) {
+            let joined = match thread.join() {
+                Ok(joined) => joined,
+                Err(payload) => Err(crate::Error::panicked(&*payload)),
+            };
+            if let Err(e) = joined {
+                log::error!("core failed: {}", e);
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Drop for Cores {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = self.join_all();
+    }
+}
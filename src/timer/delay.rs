@@ -11,21 +11,30 @@ pub struct Delay {
 }
 
 impl Delay {
-    pub fn start(reactor: reactor::Handle, duration: Duration) -> Result<Self, failure::Error> {
+    pub fn start(reactor: reactor::Handle, duration: Duration) -> Result<Self, crate::Error> {
         Ok(Self {
-            key: reactor.add_timer(duration, false),
+            key: reactor
+                .add_timer(duration, false)
+                .map_err(crate::Error::Timer)?,
             reactor,
         })
     }
 }
 
+impl Drop for Delay {
+    fn drop(&mut self) {
+        // The reactor may be gone already, along with the timer.
+        let _ = self.reactor.remove_timer(self.key);
+    }
+}
+
 impl Future for Delay {
-    type Output = Result<(), failure::Error>;
+    type Output = Result<(), crate::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match futures::ready!(self.reactor.poll_elapsed(cx, self.key)) {
             Ok(_) => Poll::Ready(Ok(())),
-            Err(e) => panic!("timer error: {}", e),
+            Err(e) => Poll::Ready(Err(crate::Error::Timer(e))),
         }
     }
 }
@@ -14,21 +14,33 @@ pub struct Timer {
 }
 
 impl Timer {
-    pub fn start(reactor: reactor::Handle, duration: Duration) -> Result<Self, failure::Error> {
+    pub fn start(reactor: reactor::Handle, duration: Duration) -> Result<Self, crate::Error> {
         Ok(Self {
-            key: reactor.add_timer(duration, true),
+            key: reactor
+                .add_timer(duration, true)
+                .map_err(crate::Error::Timer)?,
             reactor,
         })
     }
 }
 
+impl Drop for Timer {
+    fn drop(&mut self) {
+        // The reactor may be gone already, along with the timer.
+        let _ = self.reactor.remove_timer(self.key);
+    }
+}
+
 impl futures::Stream for Timer {
     type Item = ();
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         match futures::ready!(self.reactor.poll_elapsed(cx, self.key)) {
             Ok(_) => Poll::Ready(Some(())),
-            Err(e) => panic!("timer error: {}", e),
+            Err(e) => {
+                log::error!("timer error: {}", e);
+                Poll::Ready(None)
+            }
         }
     }
 }
@@ -0,0 +1,189 @@
+use dope::executor::{self, reactor, Executor};
+use dope::timer::Timer;
+
+use chrono::Duration;
+use std::os::unix::io::RawFd;
+
+struct FakeNotify;
+
+impl reactor::Notify for FakeNotify {
+    fn notify(&self) -> std::io::Result<()> {
+        // `poll` never blocks, so there is nothing to interrupt.
+        Ok(())
+    }
+}
+
+/// Fires timers on a virtual clock, without waiting for them.
+struct FakeBackend {
+    now: Duration,
+    timers: Vec<(Duration, Option<Duration>, reactor::Key)>,
+}
+
+impl FakeBackend {
+    fn new() -> Self {
+        Self {
+            now: Duration::zero(),
+            timers: vec![],
+        }
+    }
+}
+
+impl reactor::Backend for FakeBackend {
+    fn add_fd_read(&mut self, _fd: RawFd, _key: reactor::Key) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    fn add_fd_write(&mut self, _fd: RawFd, _key: reactor::Key) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    fn remove_fd(&mut self, _fd: RawFd) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    fn add_timer(
+        &mut self,
+        duration: Duration,
+        key: reactor::Key,
+        repeat: bool,
+    ) -> std::io::Result<()> {
+        let period = if repeat { Some(duration) } else { None };
+        self.timers.push((self.now + duration, period, key));
+        Ok(())
+    }
+
+    fn remove_timer(&mut self, key: reactor::Key) -> std::io::Result<()> {
+        self.timers.retain(|&(_, _, timer)| timer != key);
+        Ok(())
+    }
+
+    fn add_signal(&mut self, _signal: i32, _key: reactor::Key) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    fn notifier(&mut self, _key: reactor::Key) -> std::io::Result<reactor::Notifier> {
+        Ok(reactor::Notifier::new(FakeNotify))
+    }
+
+    fn poll(
+        &mut self,
+        events: &mut reactor::Events,
+        _timeout: Option<Duration>,
+    ) -> std::io::Result<()> {
+        let now = match self.timers.iter().map(|(deadline, _, _)| *deadline).min() {
+            Some(now) => now,
+            None => return Ok(()),
+        };
+        self.now = now;
+
+        for (deadline, period, key) in std::mem::take(&mut self.timers) {
+            if deadline > now {
+                self.timers.push((deadline, period, key));
+                continue;
+            }
+            events.push(key);
+            if let Some(period) = period {
+                self.timers.push((deadline + period, Some(period), key));
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn test_fake_backend_inner(executor: executor::Handle) -> Result<Vec<i32>, dope::Error> {
+    use futures::StreamExt;
+
+    let reactor = executor.reactor()?;
+    let stream1 = Timer::start(reactor.clone(), Duration::hours(1))?.map(|()| 1i32);
+    let stream2 = Timer::start(reactor.clone(), Duration::minutes(150))?.map(|()| 2i32);
+
+    Ok(futures::stream::select(stream1, stream2)
+        .take(6)
+        .collect()
+        .await)
+}
+
+#[test]
+fn test_poll_backend() -> Result<(), dope::Error> {
+    use futures::StreamExt;
+
+    let executor = Executor::with_backend(reactor::sys::Poll::new()?)?;
+    let reactor = executor.handle().reactor()?;
+    let stream1 = Timer::start(reactor.clone(), Duration::milliseconds(100))?.map(|()| 1i32);
+    let stream2 = Timer::start(reactor.clone(), Duration::milliseconds(250))?.map(|()| 2i32);
+    let res = executor
+        .block_on(
+            futures::stream::select(stream1, stream2)
+                .take(5)
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+    assert_eq!(res, vec![1, 1, 2, 1, 1]);
+    Ok(())
+}
+
+#[test]
+fn test_fake_backend() -> Result<(), dope::Error> {
+    let executor = Executor::with_backend(FakeBackend::new())?;
+    let handle = executor.handle();
+    let res = executor
+        .block_on(test_fake_backend_inner(handle))
+        .unwrap()?;
+    assert_eq!(res, vec![1, 1, 2, 1, 1, 2]);
+    Ok(())
+}
+
+/// Polls `backend` on a socket whose peer has reset the connection, after its
+/// write interest has been reported, which should leave nothing to wake up for.
+fn test_reset_peer<B: reactor::Backend>(mut backend: B) -> Result<(), dope::Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let stream = std::net::TcpStream::connect(listener.local_addr()?)?;
+    let (peer, _) = listener.accept()?;
+    let linger = libc::linger {
+        l_onoff: 1,
+        l_linger: 0,
+    };
+    let res = unsafe {
+        libc::setsockopt(
+            peer.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_LINGER,
+            &linger as *const libc::linger as *const _,
+            std::mem::size_of::<libc::linger>() as libc::socklen_t,
+        )
+    };
+    assert_eq!(res, 0);
+    drop(peer);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let key = reactor::Key::from(1);
+    let mut events = reactor::Events::with_capacity(16);
+    backend.add_fd_write(stream.as_raw_fd(), key)?;
+    backend.poll(&mut events, Some(Duration::milliseconds(100)))?;
+    assert_eq!(events.iter().collect::<Vec<_>>(), vec![key]);
+
+    let start = std::time::Instant::now();
+    let mut polls = 0;
+    while start.elapsed() < std::time::Duration::from_millis(300) {
+        events.clear();
+        backend.poll(&mut events, Some(Duration::milliseconds(100)))?;
+        assert!(events.is_empty());
+        polls += 1;
+    }
+    assert!(polls <= 4, "polled {} times", polls);
+    backend.remove_fd(stream.as_raw_fd())?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_reset_peer_epoll() -> Result<(), dope::Error> {
+    test_reset_peer(reactor::sys::Epoll::new()?)
+}
+
+#[test]
+fn test_reset_peer_poll() -> Result<(), dope::Error> {
+    test_reset_peer(reactor::sys::Poll::new()?)
+}
@@ -0,0 +1,266 @@
+use dope::executor::{self, Executor, FailurePolicy};
+use dope::net::TcpStream;
+use dope::timer::Delay;
+
+use chrono::Duration;
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[test]
+fn test_join_handle() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    let handle = executor.handle();
+    let reactor = handle.reactor()?;
+
+    let res = executor.block_on(async move {
+        let ready = handle.spawn(async { 1 });
+        let delayed = handle.spawn(async move {
+            Delay::start(reactor, Duration::milliseconds(10))?.await?;
+            Ok::<_, dope::Error>(2)
+        });
+        Ok::<_, dope::Error>((ready.await?, delayed.await??))
+    })??;
+    assert_eq!(res, (1, 2));
+    Ok(())
+}
+
+#[test]
+fn test_join_handle_panicked() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    let handle = executor.handle();
+
+    let res = executor.block_on(handle.spawn(async { panic!("oops") }))?;
+    match res {
+        Err(dope::Error::Panicked(message)) => assert_eq!(message, "oops"),
+        res => panic!("unexpected: {:?}", res),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_join_handle_shutdown() -> Result<(), dope::Error> {
+    let handle = Executor::new()?.handle();
+    let join = handle.spawn(async { 1 });
+
+    let executor = Executor::new()?;
+    match executor.block_on(join)? {
+        Err(dope::Error::Shutdown) => Ok(()),
+        res => panic!("unexpected: {:?}", res),
+    }
+}
+
+#[test]
+fn test_abort() -> Result<(), dope::Error> {
+    use futures::AsyncReadExt;
+    use std::io::Write;
+
+    struct Guard(Rc<Cell<bool>>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let executor = Executor::new()?;
+    let handle = executor.handle();
+    let reactor = handle.reactor()?;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let mut client = std::net::TcpStream::connect(listener.local_addr()?)?;
+    let mut stream = TcpStream::new(reactor.clone(), listener.accept()?.0)?;
+
+    let dropped = Rc::new(Cell::new(false));
+    let guard = Guard(dropped.clone());
+    let join = handle.spawn_local(async move {
+        let _guard = guard;
+        let mut buf = [0; 4];
+        stream.read(&mut buf).await
+    });
+    let abort = join.abort_handle();
+
+    let delay = Delay::start(reactor.clone(), Duration::milliseconds(10))?;
+    let res = executor.block_on(async move {
+        delay.await?;
+        abort.abort();
+        Ok::<_, dope::Error>(join.await)
+    })??;
+    match res {
+        Err(dope::Error::Cancelled) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+
+    // Aborting wakes the task, which is dropped once it is scheduled again,
+    // without waiting for the stream.
+    assert!(!dropped.get());
+    executor.block_on(Delay::start(reactor, Duration::milliseconds(10))?)??;
+    assert!(dropped.get());
+    client.write_all(b"ping")?;
+    Ok(())
+}
+
+#[test]
+fn test_failure_policy() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    let failures = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let reported = failures.clone();
+    executor.set_failure_policy(
+        FailurePolicy::new()
+            .hook(move |id, e| reported.lock().unwrap().push((id, e.to_string())))
+            .shutdown_on_failure(true),
+    );
+    let handle = executor.handle();
+
+    let ok = handle.spawn_fallible(async { Ok(1) });
+    let failed = handle.spawn_fallible(async { Err::<(), _>(dope::Error::Cancelled) });
+    let id = failed.id();
+    let res = executor.block_on(futures::future::pending::<()>());
+    match res {
+        Err(dope::Error::TaskFailed(failed)) => assert_eq!(failed, id),
+        res => panic!("unexpected: {:?}", res),
+    }
+    assert_eq!(
+        *failures.lock().unwrap(),
+        vec![(id, String::from("task cancelled"))]
+    );
+    assert_eq!(executor.block_on(ok)???, 1);
+
+    match executor.block_on(handle.spawn(async { 2 })) {
+        Ok(Err(dope::Error::Shutdown)) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_uncaught_panic() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    executor.set_failure_policy(FailurePolicy::new().catch_panics(false));
+    let handle = executor.handle();
+
+    let join = handle.spawn(async { panic!("oops") });
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| executor.block_on(join)));
+    assert!(res.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_spawn_blocking() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    let handle = executor.handle();
+    let reactor = handle.reactor()?;
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    // The delay completes while the blocking job waits for it.
+    let blocking = handle.spawn_blocking(move || receiver.recv().unwrap());
+    let res = executor.block_on(async move {
+        Delay::start(reactor, Duration::milliseconds(10))?.await?;
+        sender.send(std::thread::current().id()).unwrap();
+        blocking.await
+    })??;
+    assert_eq!(res, std::thread::current().id());
+
+    match executor.block_on(handle.spawn_blocking(|| panic!("oops")))? {
+        Err(dope::Error::Panicked(message)) => assert_eq!(message, "oops"),
+        res => panic!("unexpected: {:?}", res),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_builder() -> Result<(), dope::Error> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let started = Arc::new(AtomicUsize::new(0));
+    let counter = started.clone();
+    let executor = executor::Builder::new()
+        .event_capacity(1)
+        .budget(1)
+        .blocking_threads(1)
+        .thread_name("blocking")
+        .on_thread_start(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .build()?;
+    let handle = executor.handle();
+
+    let name = || std::thread::current().name().map(String::from);
+    let first = handle.spawn_blocking(name);
+    let second = handle.spawn_blocking(name);
+    let names = executor.block_on(async move { (first.await, second.await) })?;
+    assert_eq!(names.0?.as_deref(), Some("blocking"));
+    assert_eq!(names.1?.as_deref(), Some("blocking"));
+    assert_eq!(started.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[test]
+fn test_run() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    let handle = executor.handle();
+    let reactor = handle.reactor()?;
+    let done = Rc::new(Cell::new(0));
+
+    for i in 1..=3 {
+        let (reactor, done) = (reactor.clone(), done.clone());
+        handle.spawn_local(async move {
+            Delay::start(reactor, Duration::milliseconds(10 * i))?.await?;
+            done.set(done.get() + 1);
+            Ok::<_, dope::Error>(())
+        });
+    }
+    executor.run()?;
+    assert_eq!(done.get(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_shutdown() -> Result<(), dope::Error> {
+    struct Guard(Rc<Cell<bool>>, dope::executor::reactor::Handle);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            // The reactor outlives the tasks.
+            self.0.set(self.1.notifier().is_ok());
+        }
+    }
+
+    let executor = Executor::new()?;
+    let handle = executor.handle();
+    let reactor = handle.reactor()?;
+
+    let finished = handle.spawn(Delay::start(reactor.clone(), Duration::milliseconds(10))?);
+    let dropped = Rc::new(Cell::new(false));
+    let guard = Guard(dropped.clone(), reactor.clone());
+    let stuck = handle.spawn_local(async move {
+        let _guard = guard;
+        futures::future::pending::<()>().await
+    });
+    handle.shutdown(Duration::milliseconds(50));
+    match executor.block_on(futures::future::pending::<()>()) {
+        Err(dope::Error::Shutdown) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+    assert!(dropped.get());
+    assert!(executor.block_on(finished)?.is_ok());
+    match executor.block_on(stuck)? {
+        Err(dope::Error::Cancelled) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+    match executor.block_on(handle.spawn(async { 1 }))? {
+        Err(dope::Error::Shutdown) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+
+    // Tasks are dropped before the reactor, along with the executor.
+    let executor = Executor::new()?;
+    let dropped = Rc::new(Cell::new(false));
+    let guard = Guard(dropped.clone(), executor.handle().reactor()?);
+    executor.handle().spawn_local(async move {
+        let _guard = guard;
+        futures::future::pending::<()>().await
+    });
+    drop(executor);
+    assert!(dropped.get());
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use dope::executor::{FailurePolicy, MultiThread};
+use dope::net::TcpListener;
+use dope::timer::Delay;
+
+use chrono::Duration;
+use std::io::{Read, Write};
+use std::sync::{Arc, Barrier};
+
+#[test]
+fn test_work_stealing() -> Result<(), dope::Error> {
+    const WORKERS: usize = 4;
+
+    let runtime = MultiThread::new(WORKERS)?;
+    let handle = runtime.handle();
+    let spawner = handle.clone();
+    // The tasks are queued on a single worker, and only complete once all of
+    // them run at the same time.
+    let barrier = Arc::new(Barrier::new(WORKERS));
+    let threads = runtime.block_on(handle.spawn(async move {
+        let joins: Vec<_> = (0..WORKERS)
+            .map(|_| {
+                let barrier = barrier.clone();
+                spawner.spawn(async move {
+                    barrier.wait();
+                    std::thread::current().id()
+                })
+            })
+            .collect();
+        let mut threads = vec![];
+        for join in joins {
+            threads.push(join.await?);
+        }
+        Ok::<_, dope::Error>(threads)
+    }))???;
+    threads.iter().enumerate().for_each(|(i, thread)| {
+        assert!(!threads[..i].contains(thread));
+    });
+    Ok(())
+}
+
+#[test]
+fn test_shared_reactor() -> Result<(), dope::Error> {
+    use futures::StreamExt;
+
+    let runtime = MultiThread::new(2)?;
+    let handle = runtime.handle();
+    let reactor = handle.reactor()?;
+    let listener = TcpListener::bind(reactor.clone(), "127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let clients: Vec<_> = (0..4u8)
+        .map(|i| {
+            std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+                let mut stream = std::net::TcpStream::connect(addr)?;
+                stream.write_all(&[i])?;
+                let mut buf = vec![0; 1];
+                stream.read_exact(&mut buf)?;
+                Ok(buf)
+            })
+        })
+        .collect();
+
+    let spawner = handle.clone();
+    let server = handle.spawn(async move {
+        let mut incoming = listener.incoming().take(4);
+        while let Some(stream) = incoming.next().await {
+            let mut stream = stream?;
+            let reactor = spawner.reactor()?;
+            spawner.spawn(async move {
+                let (res, buf) = stream.read_owned(vec![0; 1]).await;
+                let n = res?;
+                Delay::start(reactor, Duration::milliseconds(10))?.await?;
+                let (res, _) = stream.write_owned(buf[..n].to_vec()).await;
+                res
+            });
+        }
+        Ok::<_, dope::Error>(())
+    });
+    runtime.block_on(server)???;
+    for (i, client) in clients.into_iter().enumerate() {
+        assert_eq!(client.join().unwrap()?, vec![i as u8]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_shutdown_on_failure() -> Result<(), dope::Error> {
+    let runtime = MultiThread::new(2)?;
+    runtime.set_failure_policy(FailurePolicy::new().shutdown_on_failure(true));
+    let handle = runtime.handle();
+
+    let failed = handle.spawn(async { panic!("boom") });
+    let id = failed.id();
+    match runtime.block_on(futures::future::pending::<()>()) {
+        Err(dope::Error::TaskFailed(failed)) => assert_eq!(failed, id),
+        res => panic!("unexpected: {:?}", res),
+    }
+    match runtime.block_on(handle.spawn(async { 1 })) {
+        Ok(Err(dope::Error::Shutdown)) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_hook_sets_policy() -> Result<(), dope::Error> {
+    use std::sync::{Mutex, Weak};
+
+    let runtime = Arc::new(MultiThread::new(2)?);
+    let weak = Arc::new(Mutex::new(Weak::<MultiThread>::new()));
+    *weak.lock().unwrap() = Arc::downgrade(&runtime);
+    runtime.set_failure_policy(FailurePolicy::new().hook(move |_, _| {
+        if let Some(runtime) = weak.lock().unwrap().upgrade() {
+            runtime.set_failure_policy(FailurePolicy::new().shutdown_on_failure(true));
+        }
+    }));
+    let handle = runtime.handle();
+
+    match runtime.block_on(handle.spawn(async { panic!("first") })) {
+        Ok(Err(dope::Error::Panicked(_))) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+    // The hook has made the executor shut down on the next failure.
+    let failed = handle.spawn(async { panic!("second") });
+    let id = failed.id();
+    match runtime.block_on(futures::future::pending::<()>()) {
+        Err(dope::Error::TaskFailed(failed)) => assert_eq!(failed, id),
+        res => panic!("unexpected: {:?}", res),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_drop_parked_tasks() -> Result<(), dope::Error> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct Guard(Arc<AtomicBool>, dope::executor::reactor::Handle);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            // The reactor outlives the tasks.
+            self.0.store(self.1.notifier().is_ok(), Ordering::SeqCst);
+        }
+    }
+
+    let runtime = MultiThread::new(2)?;
+    let handle = runtime.handle();
+    let reactor = handle.reactor()?;
+    let dropped = Arc::new(AtomicBool::new(false));
+    let guard = Guard(dropped.clone(), reactor.clone());
+    let started = Arc::new(Barrier::new(2));
+    let parked = {
+        let started = started.clone();
+        handle.spawn(async move {
+            let _guard = guard;
+            let delay = Delay::start(reactor, Duration::seconds(60))?;
+            started.wait();
+            delay.await
+        })
+    };
+    started.wait();
+    drop(runtime);
+    assert!(dropped.load(Ordering::SeqCst));
+    drop(parked);
+    Ok(())
+}
@@ -0,0 +1,393 @@
+use dope::executor::Executor;
+use dope::net::{TcpListener, TcpStream};
+
+use std::io::{Read, Write};
+
+fn test_owned_buffers(executor: Executor) -> Result<(), dope::Error> {
+    use futures::StreamExt;
+
+    let reactor = executor.handle().reactor()?;
+    let listener = TcpListener::bind(reactor, "127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let client = std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+        let mut stream = std::net::TcpStream::connect(addr)?;
+        stream.write_all(b"ping")?;
+        let mut buf = vec![0; 4];
+        stream.read_exact(&mut buf)?;
+        Ok(buf)
+    });
+
+    executor
+        .block_on(async move {
+            let mut stream = listener.incoming().next().await.unwrap()?;
+            let (res, buf) = stream.read_owned(vec![0; 4]).await;
+            assert_eq!(&buf[..res?], b"ping");
+            let (res, _) = stream.write_owned(b"pong".to_vec()).await;
+            assert_eq!(res?, 4);
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()?;
+    assert_eq!(client.join().unwrap()?, b"pong");
+    Ok(())
+}
+
+#[test]
+fn test_owned_buffers_readiness() -> Result<(), dope::Error> {
+    test_owned_buffers(Executor::new()?)
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+#[test]
+fn test_owned_buffers_uring() -> Result<(), dope::Error> {
+    test_owned_buffers(Executor::with_backend(
+        dope::executor::reactor::sys::Uring::new()?,
+    )?)
+}
+
+#[test]
+fn test_connect() -> Result<(), dope::Error> {
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    let executor = Executor::new()?;
+    let reactor = executor.handle().reactor()?;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let refused = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    let server = std::thread::spawn(move || -> std::io::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf)?;
+        stream.write_all(&buf)
+    });
+
+    executor
+        .block_on(async move {
+            let err = TcpStream::connect(reactor.clone(), refused).await.err();
+            assert!(err.is_some());
+            // Falls back to the next address.
+            let mut stream = TcpStream::connect(reactor, &[refused, addr][..]).await?;
+            stream.write_all(b"ping").await?;
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await?;
+            assert_eq!(&buf, b"ping");
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()?;
+    server.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_backpressure() -> Result<(), dope::Error> {
+    use futures::AsyncWriteExt;
+
+    const LEN: usize = 16 * 1024 * 1024;
+
+    let executor = Executor::new()?;
+    let reactor = executor.handle().reactor()?;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let server = std::thread::spawn(move || -> std::io::Result<usize> {
+        let (mut stream, _) = listener.accept()?;
+        // Let the socket buffers fill up first.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf)
+    });
+
+    executor
+        .block_on(async move {
+            let mut stream = TcpStream::connect(reactor, addr).await?;
+            stream.write_all(&vec![0; LEN]).await?;
+            stream.flush().await?;
+            stream.close().await?;
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()?;
+    assert_eq!(server.join().unwrap()?, LEN);
+    Ok(())
+}
+
+fn test_full_duplex(executor: Executor) -> Result<(), dope::Error> {
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    const LEN: usize = 8 * 1024 * 1024;
+
+    let handle = executor.handle();
+    let reactor = handle.reactor()?;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    // Echoes back, so that it only reads on while the client reads too.
+    let server = std::thread::spawn(move || -> std::io::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match stream.read(&mut buf)? {
+                0 => return Ok(()),
+                len => stream.write_all(&buf[..len])?,
+            }
+        }
+    });
+
+    executor
+        .block_on(async move {
+            let stream = TcpStream::connect(reactor, addr).await?;
+            // The halves of `futures`, which lock the stream in turns.
+            let (mut reader, mut writer) = AsyncReadExt::split(stream);
+            // Neither half runs on the root task, which is polled more often.
+            let written = handle.spawn_local(async move {
+                writer.write_all(&vec![1; LEN]).await?;
+                Ok::<_, std::io::Error>(writer)
+            });
+            let read = handle.spawn_local(async move {
+                let mut buf = vec![0; LEN];
+                reader.read_exact(&mut buf).await?;
+                assert!(buf.iter().all(|&byte| byte == 1));
+                Ok::<_, std::io::Error>(())
+            });
+            read.await??;
+            written.await??.close().await?;
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()?;
+    server.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn test_full_duplex_readiness() -> Result<(), dope::Error> {
+    test_full_duplex(Executor::new()?)
+}
+
+#[test]
+fn test_full_duplex_poll() -> Result<(), dope::Error> {
+    test_full_duplex(Executor::with_backend(
+        dope::executor::reactor::sys::Poll::new()?,
+    )?)
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+#[test]
+fn test_full_duplex_uring() -> Result<(), dope::Error> {
+    test_full_duplex(Executor::with_backend(
+        dope::executor::reactor::sys::Uring::new()?,
+    )?)
+}
+
+/// Echoes back until the client stops writing.
+fn echo_server() -> std::io::Result<(
+    std::net::SocketAddr,
+    std::thread::JoinHandle<std::io::Result<()>>,
+)> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept()?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match stream.read(&mut buf)? {
+                0 => return Ok(()),
+                len => stream.write_all(&buf[..len])?,
+            }
+        }
+    });
+    Ok((addr, server))
+}
+
+#[test]
+fn test_split() -> Result<(), dope::Error> {
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    const LEN: usize = 8 * 1024 * 1024;
+
+    let executor = Executor::new()?;
+    let reactor = executor.handle().reactor()?;
+    let (addr, server) = echo_server()?;
+
+    executor
+        .block_on(async move {
+            let mut stream = TcpStream::connect(reactor, addr).await?;
+            // Not `AsyncReadExt::split`, which takes the stream by value.
+            let (mut reader, mut writer) = TcpStream::split(&mut stream);
+            let write = async {
+                writer.write_all(&vec![1; LEN]).await?;
+                writer.close().await
+            };
+            let read = async {
+                let mut buf = vec![];
+                reader.read_to_end(&mut buf).await?;
+                Ok::<_, std::io::Error>(buf)
+            };
+            let (written, read) = futures::future::join(write, read).await;
+            written?;
+            let buf = read?;
+            assert_eq!(buf.len(), LEN);
+            assert!(buf.iter().all(|&byte| byte == 1));
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()?;
+    server.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn test_into_split() -> Result<(), dope::Error> {
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    const LEN: usize = 8 * 1024 * 1024;
+
+    let executor = Executor::new()?;
+    let handle = executor.handle();
+    let reactor = handle.reactor()?;
+    let (addr, server) = echo_server()?;
+
+    executor
+        .block_on(async move {
+            let stream = TcpStream::connect(reactor, addr).await?;
+            let (mut reader, mut writer) = stream.into_split();
+            let written = handle.spawn(async move {
+                writer.write_all(&vec![1; LEN]).await?;
+                Ok::<_, std::io::Error>(writer)
+            });
+            let read = handle.spawn(async move {
+                let mut buf = vec![0; LEN];
+                reader.read_exact(&mut buf).await?;
+                assert!(buf.iter().all(|&byte| byte == 1));
+                Ok::<_, std::io::Error>(reader)
+            });
+            let reader = read.await??;
+            let mut stream = reader.reunite(written.await??).ok().unwrap();
+            stream.close().await?;
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()?;
+    server.join().unwrap()?;
+    Ok(())
+}
+
+#[test]
+fn test_reunite_error() -> Result<(), dope::Error> {
+    use dope::net::ReuniteError;
+
+    let executor = Executor::new()?;
+    let reactor = executor.handle().reactor()?;
+    // Left in the backlog, which is enough to connect.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    executor
+        .block_on(async move {
+            let first = TcpStream::connect(reactor.clone(), addr).await?;
+            let second = TcpStream::connect(reactor, addr).await?;
+            let (first_read, first_write) = first.into_split();
+            let (second_read, second_write) = second.into_split();
+            let (first_read, second_write) = match first_read.reunite(second_write) {
+                Err(ReuniteError(read, write)) => (read, write),
+                Ok(_) => panic!("reunited halves of different streams"),
+            };
+            assert!(first_read.reunite(first_write).is_ok());
+            assert!(second_write.reunite(second_read).is_ok());
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_half_close() -> Result<(), dope::Error> {
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    let executor = Executor::new()?;
+    let reactor = executor.handle().reactor()?;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    // Replies once the client has stopped writing.
+    let server = std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+        let (mut stream, _) = listener.accept()?;
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf)?;
+        stream.write_all(b"pong")?;
+        Ok(buf)
+    });
+
+    executor
+        .block_on(async move {
+            let mut stream = TcpStream::connect(reactor, addr).await?;
+            let (mut reader, mut writer) = TcpStream::split(&mut stream);
+            writer.write_all(b"ping").await?;
+            writer.close().await?;
+            let mut buf = vec![];
+            reader.read_to_end(&mut buf).await?;
+            assert_eq!(buf, b"pong");
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()?;
+    assert_eq!(server.join().unwrap()?, b"ping");
+    Ok(())
+}
+
+#[test]
+fn test_tcp_socket() -> Result<(), dope::Error> {
+    use dope::net::{Keepalive, TcpSocket};
+    use futures::StreamExt;
+
+    let executor = Executor::new()?;
+    let reactor = executor.handle().reactor()?;
+    let socket = TcpSocket::new_v4()?;
+    socket.set_reuseaddr(true)?;
+    socket.set_recv_buffer_size(64 * 1024)?;
+    socket.bind(&"127.0.0.1:0".parse().unwrap())?;
+    let addr = socket.local_addr()?;
+    let listener = socket.listen(reactor.clone(), 16)?;
+
+    executor
+        .block_on(async move {
+            let socket = TcpSocket::new_v4()?;
+            socket.set_nodelay(true)?;
+            socket.set_send_buffer_size(64 * 1024)?;
+            socket.set_linger(Some(chrono::Duration::seconds(1)))?;
+            socket.set_keepalive(Some(
+                Keepalive::new()
+                    .time(chrono::Duration::seconds(60))
+                    .interval(chrono::Duration::seconds(10))
+                    .retries(3),
+            ))?;
+            // Bound before connecting, to pick the local address.
+            socket.bind(&"127.0.0.1:0".parse().unwrap())?;
+            let local = socket.local_addr()?;
+            let client = socket.connect(reactor, addr).await?;
+            assert_eq!(client.local_addr()?, local);
+            assert_eq!(client.peer_addr()?, addr);
+            assert!(client.nodelay()?);
+            client.set_ttl(42)?;
+            assert_eq!(client.ttl()?, 42);
+
+            let server = listener.incoming().next().await.unwrap()?;
+            assert_eq!(server.peer_addr()?, local);
+            assert!(!server.nodelay()?);
+            server.set_nodelay(true)?;
+            assert!(server.nodelay()?);
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_connect_timeout_releases_timer() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    let reactor = executor.handle().reactor()?;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    let registrations = reactor.registrations()?;
+    executor
+        .block_on(async move {
+            for _ in 0..64 {
+                let timeout = chrono::Duration::seconds(10);
+                TcpStream::connect_timeout(reactor.clone(), addr, timeout).await?;
+                listener.accept()?;
+            }
+            assert_eq!(reactor.registrations()?, registrations);
+            Ok::<_, dope::Error>(())
+        })
+        .unwrap()
+}
@@ -0,0 +1,84 @@
+use dope::executor::reactor::{self, Reactor};
+use dope::executor::Executor;
+use dope::timer::Delay;
+
+use chrono::Duration;
+use std::task::Poll;
+use std::time::Instant;
+
+#[test]
+fn test_poll_timeout() -> Result<(), dope::Error> {
+    let mut reactor = Reactor::new()?;
+
+    let start = Instant::now();
+    reactor.turn()?;
+    assert!(start.elapsed() < std::time::Duration::from_millis(50));
+
+    let start = Instant::now();
+    reactor.poll(Some(Duration::milliseconds(100)))?;
+    assert!(start.elapsed() >= std::time::Duration::from_millis(100));
+    Ok(())
+}
+
+fn test_notifier(executor: Executor) -> Result<(), dope::Error> {
+    let notifier = executor.handle().reactor()?.notifier()?;
+    let notifying = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        notifier.notify()
+    });
+
+    // Nothing but the notifier can make the loop poll this future again.
+    let mut polled = false;
+    executor.block_on(futures::future::poll_fn(|_| {
+        if std::mem::replace(&mut polled, true) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }))?;
+    Ok(notifying.join().unwrap()?)
+}
+
+#[test]
+fn test_notifier_selector() -> Result<(), dope::Error> {
+    test_notifier(Executor::new()?)
+}
+
+#[test]
+fn test_notifier_poll() -> Result<(), dope::Error> {
+    test_notifier(Executor::with_backend(reactor::sys::Poll::new()?)?)
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+#[test]
+fn test_notifier_uring() -> Result<(), dope::Error> {
+    test_notifier(Executor::with_backend(reactor::sys::Uring::new()?)?)
+}
+
+#[test]
+fn test_small_event_capacity() -> Result<(), dope::Error> {
+    let reactor = reactor::Builder::new()
+        .event_capacity(1)
+        .max_event_capacity(2)
+        .build()?;
+    let executor = Executor::from_reactor(reactor);
+    let reactor = executor.handle().reactor()?;
+
+    let delays = (0..4)
+        .map(|_| Delay::start(reactor.clone(), Duration::milliseconds(10)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let res = executor.block_on(futures::future::join_all(delays))?;
+    assert_eq!(res.len(), 4);
+    Ok(())
+}
+
+#[test]
+fn test_reactor_gone() -> Result<(), dope::Error> {
+    let executor = Executor::new()?;
+    let handle = executor.handle().reactor()?;
+    drop(executor);
+    match handle.add_timer(Duration::milliseconds(10), false) {
+        Err(reactor::Error::Gone) => Ok(()),
+        res => panic!("unexpected: {:?}", res.map(|_| ())),
+    }
+}
@@ -0,0 +1,69 @@
+use dope::executor::Executor;
+use dope::net::TcpListener;
+use dope::runtime::PerCore;
+
+use std::io::Read;
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+#[test]
+fn test_per_core() -> Result<(), dope::Error> {
+    use futures::StreamExt;
+
+    const THREADS: usize = 2;
+
+    let addr = {
+        let executor = Executor::new()?;
+        TcpListener::bind_reuse_port(executor.handle().reactor()?, "127.0.0.1:0")?.local_addr()?
+    };
+    let (ready, bound) = mpsc::channel();
+    let ready = Mutex::new(ready);
+    let cores = PerCore::new().threads(THREADS).start(move |core| {
+        let ready = ready.lock().unwrap().clone();
+        async move {
+            let listener = TcpListener::bind_reuse_port(core.handle().reactor()?, addr)?;
+            ready.send(()).unwrap();
+            let mut incoming = listener.incoming();
+            while let Some(stream) = incoming.next().await {
+                let mut stream = stream?;
+                let index = core.index() as u8;
+                core.handle().spawn(async move {
+                    let (res, _) = stream.write_owned(vec![index]).await;
+                    res
+                });
+            }
+            Ok(())
+        }
+    })?;
+    assert_eq!(cores.len(), THREADS);
+    for _ in 0..THREADS {
+        bound.recv().unwrap();
+    }
+
+    for _ in 0..8 {
+        let mut stream = std::net::TcpStream::connect(addr)?;
+        let mut buf = [0; 1];
+        stream.read_exact(&mut buf)?;
+        assert!((buf[0] as usize) < THREADS);
+    }
+    cores.stop();
+    cores.join()
+}
+
+#[test]
+fn test_per_core_failure() {
+    let cores = PerCore::new()
+        .threads(2)
+        .pin(false)
+        .start(|core| async move {
+            match core.index() {
+                0 => Ok(()),
+                _ => Err(dope::Error::Cancelled),
+            }
+        })
+        .unwrap();
+    match cores.join() {
+        Err(dope::Error::Cancelled) => {}
+        res => panic!("unexpected: {:?}", res),
+    }
+}
@@ -0,0 +1,93 @@
+use dope::executor::reactor::{Notifier, Notify};
+use dope::executor::scheduler::Scheduler;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Poll, Waker};
+
+#[derive(Default)]
+struct CountingNotify(Arc<AtomicUsize>);
+
+impl Notify for CountingNotify {
+    fn notify(&self) -> std::io::Result<()> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_wakers() {
+    let notified = Arc::new(AtomicUsize::new(0));
+    let scheduler = Scheduler::new(Notifier::new(CountingNotify(notified.clone())));
+    let wakers: Rc<RefCell<Vec<Waker>>> = Default::default();
+
+    let stored = wakers.clone();
+    let mut polls = 0;
+    scheduler.schedule(Box::pin(futures::future::poll_fn(move |cx| {
+        polls += 1;
+        if polls == 3 {
+            return Poll::Ready(());
+        }
+        let waker = cx.waker().clone();
+        if polls == 1 {
+            waker.wake_by_ref();
+        }
+        stored.borrow_mut().push(waker.clone());
+        stored.borrow_mut().push(waker);
+        Poll::Pending
+    })));
+
+    // Woken from within its own poll.
+    scheduler.tick();
+    assert!(!scheduler.is_empty());
+    scheduler.tick();
+    assert_eq!(wakers.borrow().len(), 4);
+
+    // Woken from another thread while parked.
+    assert!(scheduler.park());
+    let waker = wakers.borrow_mut().pop().unwrap();
+    std::thread::spawn(move || waker.wake()).join().unwrap();
+    scheduler.unpark();
+    assert_eq!(notified.load(Ordering::SeqCst), 1);
+    scheduler.tick();
+    assert!(scheduler.is_empty());
+
+    // Wakers outliving their task and the scheduler.
+    for waker in wakers.borrow().iter() {
+        waker.wake_by_ref();
+    }
+    scheduler.tick();
+    let wakers = std::mem::take(&mut *wakers.borrow_mut());
+    drop(scheduler);
+    for waker in wakers {
+        let cloned = waker.clone();
+        drop(waker);
+        cloned.wake();
+    }
+}
+
+#[test]
+fn test_run_budget() {
+    let scheduler = Scheduler::new(Notifier::new(CountingNotify::default()));
+    for _ in 0..2 {
+        let mut polls = 0;
+        scheduler.schedule(Box::pin(futures::future::poll_fn(move |cx| {
+            polls += 1;
+            if polls == 3 {
+                return Poll::Ready(());
+            }
+            // Queued once, however many times it is woken.
+            cx.waker().wake_by_ref();
+            let waker = cx.waker().clone();
+            std::thread::spawn(move || waker.wake()).join().unwrap();
+            Poll::Pending
+        })));
+    }
+
+    assert_eq!(scheduler.run(1), 1);
+    assert_eq!(scheduler.run(100), 5);
+    assert!(scheduler.is_empty());
+    assert_eq!(scheduler.run(100), 0);
+}
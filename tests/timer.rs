@@ -3,7 +3,7 @@ use dope::timer::Timer;
 
 use chrono::Duration;
 
-async fn test_timer_inner(executor: executor::Handle) -> Result<Vec<i32>, failure::Error> {
+async fn test_timer_inner(executor: executor::Handle) -> Result<Vec<i32>, dope::Error> {
     use futures::StreamExt;
 
     let reactor = executor.reactor()?;
@@ -17,7 +17,7 @@ async fn test_timer_inner(executor: executor::Handle) -> Result<Vec<i32>, failur
 }
 
 #[test]
-fn test_timer() -> Result<(), failure::Error> {
+fn test_timer() -> Result<(), dope::Error> {
     let executor = Executor::new()?;
     let handle = executor.handle();
     let res = executor.block_on(test_timer_inner(handle)).unwrap()?;